@@ -37,6 +37,8 @@ fn cmd_main() -> Result<()> {
     let mut app = build::context_from_config(config, &options)?;
 
     match options.command.clone() {
+        cli::Command::Cache(cache) => cmds::cache::main(&mut app, &cache),
+        cli::Command::Check(check) => cmds::check::main(&mut app, &check),
         cli::Command::Cmd(cmd) => cmds::cmd::main_cmd(&mut app, &cmd),
         cli::Command::Completion(_) => Ok(()), // Handled above
         cli::Command::Custom(args) => cmds::cmd::main_custom(&mut app, &args),
@@ -46,9 +48,12 @@ fn cmd_main() -> Result<()> {
         cli::Command::Init(_) => Ok(()), // Handled above
         cli::Command::Inspect(mut inspect) => cmds::inspect::main(&mut app, &mut inspect),
         cli::Command::List(list) => cmds::list::main(&mut app, &list),
+        cli::Command::Lock(lock) => cmds::lock::main(&mut app, &lock),
         cli::Command::Plant(plant) => cmds::plant::main(&mut app, &plant),
         cli::Command::Prune(mut prune) => cmds::prune::main(&mut app, &mut prune),
+        cli::Command::Query(query) => cmds::query::main(&mut app, &query),
         cli::Command::Shell(shell) => cmds::shell::main(&mut app, &shell),
+        cli::Command::Status(status) => cmds::status::main(&mut app, &status),
     }
 }
 
@@ -92,6 +92,10 @@ pub fn debug_level(debug: &[String], name: &str) -> u8 {
 
 #[derive(Clone, Debug, Subcommand)]
 pub enum Command {
+    /// Manage the repository cache
+    Cache(cmds::cache::CacheOptions),
+    /// Compare trees on disk against the configuration
+    Check(cmds::check::CheckOptions),
     /// Run custom commands over gardens
     Cmd(cmds::cmd::CmdOptions),
     /// Generate shell completions
@@ -112,13 +116,19 @@ pub enum Command {
     /// List available gardens, groups, trees and commands
     #[command(alias = "ls")]
     List(cmds::list::ListOptions),
+    /// Record the current state of trees in "garden.lock"
+    Lock(cmds::lock::LockOptions),
     /// Add pre-existing worktrees to a garden configuration file
     Plant(cmds::plant::PlantOptions),
     /// Remove unreferenced Git repositories
     Prune(cmds::prune::PruneOptions),
+    /// Resolve tree queries into trees
+    Query(cmds::query::QueryOptions),
     /// Open a shell in a garden environment
     #[command(alias = "sh")]
     Shell(cmds::shell::ShellOptions),
+    /// Report the Git status of trees
+    Status(cmds::status::StatusOptions),
 }
 
 impl std::default::Default for Command {
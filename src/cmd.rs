@@ -2,6 +2,15 @@ use super::errors;
 use super::eval;
 use super::model;
 
+use std::io::{BufRead, Read, Write};
+use std::sync::Mutex;
+
+/// Serializes the display of buffered output blocks.
+static OUTPUT_LOCK: Mutex<()> = Mutex::new(());
+
+/// How long to wait for the output of a command that was killed after timing out.
+const OUTPUT_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_millis(500);
+
 /// Return a subprocess::Exec instance from a command vector.
 pub fn run<S>(cmd: &[S]) -> Result<(), errors::GardenError>
 where
@@ -109,6 +118,8 @@ where
 /// - context: Reference to the TreeContext to evaluate.
 /// - quiet: Suppress messages when set true.
 /// - verbose: increase verbosity of messages.
+/// - output: Controls how the command's output is displayed.
+/// - dry_run: Print the command that would be run instead of running it.
 /// - command: String vector of the command to run.
 
 pub fn exec_in_context<S>(
@@ -116,35 +127,319 @@ pub fn exec_in_context<S>(
     context: &model::TreeContext,
     quiet: bool,
     verbose: u8,
+    output: model::OutputMode,
+    dry_run: bool,
     command: &[S],
 ) -> Result<(), errors::GardenError>
 where
     S: AsRef<std::ffi::OsStr>,
 {
     let path;
+    let header;
     if let Some(tree) = config.trees.get(&context.tree) {
         path = tree.path_as_ref()?;
 
         // Sparse gardens/missing trees are ok -> skip these entries.
-        if !model::print_tree(tree, verbose, quiet) {
+        if !std::path::PathBuf::from(&path).exists() {
+            if !quiet {
+                eprintln!("{}", model::display_missing_tree(tree, path, verbose));
+            }
             return Ok(());
         }
+        header = model::display_tree(tree, path, verbose);
     } else {
         return Ok(());
     }
+    // Buffered output is displayed along with the header once the command completes.
+    if !quiet && output != model::OutputMode::Block {
+        eprintln!("{header}");
+    }
     // Evaluate the tree environment and run the command.
     let env = eval::environment(config, context);
     let command_vec = resolve_command(command, &env);
 
-    // Create an Exec object.
-    let mut exec = exec_in_dir(&command_vec, path);
+    let mut buffer = String::new();
+    if dry_run {
+        let mut lines = dry_run_preamble(path, &env);
+        lines.push(quote_command(&command_vec));
+        for line in &lines {
+            output_line(output, &context.tree, &mut buffer, &display_command(line));
+        }
+        if output == model::OutputMode::Block {
+            print_block(if quiet { None } else { Some(&header) }, &buffer);
+        }
+        return Ok(());
+    }
+
+    // Commands are run with $PWD set to the tree's path. The tree environment can override it.
+    let mut command_env = vec![(string!("PWD"), path.clone())];
+    command_env.extend(env);
+
+    let exit_status = join_with_output(
+        &command_vec,
+        path,
+        &command_env,
+        output,
+        &context.tree,
+        &mut buffer,
+        None,
+    );
+    if output == model::OutputMode::Block {
+        print_block(if quiet { None } else { Some(&header) }, &buffer);
+    }
+
+    result_from_exit_status(exit_status)
+}
+
+/// Run a command and return its exit status. Output is displayed according to
+/// the OutputMode. "Prefix" mode prefixes each line with the specified name.
+/// "Block" mode captures stdout and stderr into the buffer for display by the caller.
+/// Commands that run longer than the optional timeout are killed and
+/// errors::EX_TIMEOUT is returned.
+///
+/// The command is run in the specified directory with the environment variables added
+/// to the current environment. Commands with a timeout whose output is captured are
+/// started in a new process group so that the processes that they start are also killed
+/// when they time out. Background process groups cannot read from the terminal so their
+/// input is redirected from /dev/null.
+pub fn join_with_output<S>(
+    command: &[S],
+    path: &str,
+    env: &[(String, String)],
+    output: model::OutputMode,
+    name: &str,
+    buffer: &mut String,
+    timeout: Option<std::time::Duration>,
+) -> i32
+where
+    S: AsRef<std::ffi::OsStr>,
+{
+    let mut command_env = subprocess::PopenConfig::current_env();
+    for (env_name, value) in env {
+        command_env.retain(|(existing_name, _)| existing_name.as_os_str() != env_name.as_str());
+        command_env.push((env_name.into(), value.into()));
+    }
+    let process_group = timeout.is_some() && output != model::OutputMode::Inherit;
+    let stdin = if process_group {
+        std::fs::File::open("/dev/null")
+            .map(subprocess::Redirection::File)
+            .unwrap_or(subprocess::Redirection::None)
+    } else {
+        subprocess::Redirection::None
+    };
+    let config = subprocess::PopenConfig {
+        cwd: Some(path.into()),
+        env: Some(command_env),
+        stdin,
+        setpgid: process_group,
+        ..subprocess::PopenConfig::default()
+    };
+    match output {
+        model::OutputMode::Inherit => match subprocess::Popen::create(command, config) {
+            Ok(mut popen) => wait_with_timeout(&mut popen, timeout, false),
+            Err(_) => errors::EX_ERROR,
+        },
+        model::OutputMode::Prefix => join_with_prefix(command, config, name, timeout),
+        model::OutputMode::Block => join_with_block(command, config, buffer, timeout),
+    }
+}
+
+/// Wait for a child process to exit. Processes that outlive the timeout are killed.
+/// Every process in the child's process group is also killed when "process_group" is true.
+fn wait_with_timeout(
+    popen: &mut subprocess::Popen,
+    timeout: Option<std::time::Duration>,
+    process_group: bool,
+) -> i32 {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return status(popen.wait()),
+    };
+    match popen.wait_timeout(timeout) {
+        Ok(Some(exit_status)) => status(Ok(exit_status)),
+        Ok(None) => {
+            // The process group ID of a child started using setpgid(0, 0) is its process ID.
+            if let (true, Some(pid)) = (process_group, popen.pid()) {
+                kill_process_group(pid);
+            }
+            popen.kill().unwrap_or(());
+            popen.wait().unwrap_or(subprocess::ExitStatus::Undetermined);
+            errors::EX_TIMEOUT
+        }
+        Err(_) => errors::EX_ERROR,
+    }
+}
+
+/// Kill every process in a process group using kill(1).
+fn kill_process_group(pgid: u32) {
+    let group = format!("-{pgid}");
+    subprocess::Exec::cmd("kill")
+        .args(&["-s", "KILL", "--", &group])
+        .stdout(subprocess::NullFile)
+        .stderr(subprocess::NullFile)
+        .join()
+        .ok();
+}
+
+/// Wait for a thread that is reading output from a child process.
+/// Processes that leave the process group of a child that was killed, e.g. daemons,
+/// can keep its output pipes open, so the thread is abandoned if it does not finish
+/// within a short grace period.
+fn join_output_thread(handle: std::thread::JoinHandle<()>, exit_status: i32) {
+    if exit_status == errors::EX_TIMEOUT {
+        let deadline = std::time::Instant::now() + OUTPUT_GRACE_PERIOD;
+        while !handle.is_finished() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        if !handle.is_finished() {
+            return;
+        }
+    }
+    handle.join().unwrap_or(());
+}
+
+/// Return the prefix used for lines of output in "Prefix" mode.
+pub fn output_prefix(name: &str) -> String {
+    model::Color::blue(format!("{name}:")).bold().to_string()
+}
+
+/// Display a line of output generated by garden according to the OutputMode.
+/// "Block" mode appends the line to the buffer for display by the caller.
+pub fn output_line(output: model::OutputMode, name: &str, buffer: &mut String, line: &str) {
+    match output {
+        model::OutputMode::Inherit => println!("{line}"),
+        model::OutputMode::Prefix => println!("{} {}", output_prefix(name), line),
+        model::OutputMode::Block => {
+            buffer.push_str(line);
+            buffer.push('\n');
+        }
+    }
+}
+
+/// Display a diagnostic message generated by garden according to the OutputMode.
+/// Messages are printed to stderr. "Block" mode appends the message to the buffer.
+pub fn output_message(output: model::OutputMode, name: &str, buffer: &mut String, line: &str) {
+    match output {
+        model::OutputMode::Inherit => eprintln!("{line}"),
+        model::OutputMode::Prefix => eprintln!("{} {}", output_prefix(name), line),
+        model::OutputMode::Block => {
+            buffer.push_str(line);
+            buffer.push('\n');
+        }
+    }
+}
+
+/// Run a command and stream its output with each line prefixed by the specified name.
+/// Lines from stdout are printed to stdout and lines from stderr are printed to stderr.
+fn join_with_prefix<S>(
+    command: &[S],
+    config: subprocess::PopenConfig,
+    name: &str,
+    timeout: Option<std::time::Duration>,
+) -> i32
+where
+    S: AsRef<std::ffi::OsStr>,
+{
+    let config = subprocess::PopenConfig {
+        stdout: subprocess::Redirection::Pipe,
+        stderr: subprocess::Redirection::Pipe,
+        ..config
+    };
+    let process_group = config.setpgid;
+    let mut popen = match subprocess::Popen::create(command, config) {
+        Ok(popen) => popen,
+        Err(_) => return errors::EX_ERROR,
+    };
+    let mut handles = Vec::new();
+    if let Some(stderr) = popen.stderr.take() {
+        let prefix = output_prefix(name);
+        handles.push(std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stderr)
+                .lines()
+                .map_while(Result::ok)
+            {
+                eprintln!("{prefix} {line}");
+            }
+        }));
+    }
+    if let Some(stdout) = popen.stdout.take() {
+        let prefix = output_prefix(name);
+        handles.push(std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stdout)
+                .lines()
+                .map_while(Result::ok)
+            {
+                println!("{prefix} {line}");
+            }
+        }));
+    }
 
-    //  Update the command environment
-    for (name, value) in &env {
-        exec = exec.env(name, value);
+    let exit_status = wait_with_timeout(&mut popen, timeout, process_group);
+    for handle in handles {
+        join_output_thread(handle, exit_status);
     }
 
-    result_from_exit_status(status(exec.join()))
+    exit_status
+}
+
+/// Run a command and capture its stdout and stderr into the buffer.
+fn join_with_block<S>(
+    command: &[S],
+    config: subprocess::PopenConfig,
+    buffer: &mut String,
+    timeout: Option<std::time::Duration>,
+) -> i32
+where
+    S: AsRef<std::ffi::OsStr>,
+{
+    let config = subprocess::PopenConfig {
+        stdout: subprocess::Redirection::Pipe,
+        stderr: subprocess::Redirection::Merge,
+        ..config
+    };
+    let process_group = config.setpgid;
+    let mut popen = match subprocess::Popen::create(command, config) {
+        Ok(popen) => popen,
+        Err(_) => return errors::EX_ERROR,
+    };
+    let captured = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let mut handles = Vec::new();
+    if let Some(mut stdout) = popen.stdout.take() {
+        let captured = captured.clone();
+        handles.push(std::thread::spawn(move || {
+            let mut chunk = [0u8; 4096];
+            while let Ok(size) = stdout.read(&mut chunk) {
+                if size == 0 {
+                    break;
+                }
+                if let Ok(mut captured) = captured.lock() {
+                    captured.extend_from_slice(&chunk[..size]);
+                }
+            }
+        }));
+    }
+
+    let exit_status = wait_with_timeout(&mut popen, timeout, process_group);
+    for handle in handles {
+        join_output_thread(handle, exit_status);
+    }
+    if let Ok(captured) = captured.lock() {
+        buffer.push_str(&String::from_utf8_lossy(&captured));
+    }
+
+    exit_status
+}
+
+/// Print an optional header and a block of buffered output.
+/// Blocks printed from multiple threads are never interleaved.
+pub fn print_block(header: Option<&str>, buffer: &str) {
+    let _lock = OUTPUT_LOCK.lock();
+    if let Some(header) = header {
+        eprintln!("{header}");
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(buffer.as_bytes()).unwrap_or(());
+    stdout.flush().unwrap_or(());
 }
 
 /// The command might be a path that only exists inside the resolved
@@ -191,6 +486,61 @@ where
     command_vec
 }
 
+/// Initialize the global thread pool used for running jobs in parallel.
+/// A value of zero uses the number of available cores.
+pub fn initialize_threads(num_jobs: usize) {
+    // The global thread pool can only be initialized once. Subsequent calls are no-ops.
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_jobs)
+        .build_global()
+        .unwrap_or(());
+}
+
+/// Quote a command vector into a string for display.
+pub fn quote_command<S: AsRef<str>>(command: &[S]) -> String {
+    command
+        .iter()
+        .map(|arg| shlex::quote(arg.as_ref()).to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Return the variables from an evaluated environment whose final values differ from
+/// the current process environment. Variables are returned in the order they were first set.
+pub fn environment_delta(env: &[(String, String)]) -> Vec<(String, String)> {
+    let mut values: indexmap::IndexMap<&str, &str> = indexmap::IndexMap::new();
+    for (name, value) in env {
+        values.insert(name, value);
+    }
+    values
+        .into_iter()
+        .filter(|(name, value)| match std::env::var(name) {
+            Ok(current) => current != *value,
+            Err(_) => true,
+        })
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Describe the working directory and environment used by a dry-run as shell commands.
+pub fn dry_run_preamble(path: &str, env: &[(String, String)]) -> Vec<String> {
+    let mut lines = vec![quote_command(&["cd", path])];
+    for (name, value) in environment_delta(env) {
+        lines.push(format!("export {}={}", name, shlex::quote(&value)));
+    }
+
+    lines
+}
+
+/// Format a command string for display as a command that is about to run.
+pub fn display_command(command: &str) -> String {
+    format!(
+        "{} {}",
+        model::Color::cyan(":"),
+        model::Color::green(command)
+    )
+}
+
 /// Return the current executable path.
 pub fn current_exe() -> String {
     match std::env::current_exe() {
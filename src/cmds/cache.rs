@@ -0,0 +1,174 @@
+use super::super::cmd;
+use super::super::errors;
+use super::super::eval;
+use super::super::model;
+use super::super::query;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use std::collections::HashSet;
+
+/// Manage the repository cache
+#[derive(Parser, Clone, Debug)]
+#[command(author, about, long_about)]
+pub struct CacheOptions {
+    #[command(subcommand)]
+    command: CacheCommand,
+}
+
+/// Subcommands for "garden cache"
+#[derive(Clone, Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Create and update the bare mirrors for the remote URLs of trees
+    Update(CacheUpdateOptions),
+}
+
+/// Create and update the bare mirrors for the remote URLs of trees
+#[derive(Parser, Clone, Debug)]
+pub struct CacheUpdateOptions {
+    /// Number of mirrors to update in parallel. "0" uses all available cores
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    num_jobs: usize,
+    /// Tree queries for the trees whose mirrors are updated [default: all trees]
+    queries: Vec<String>,
+}
+
+/// Main entry point for the "garden cache" command
+pub fn main(app: &mut model::ApplicationContext, options: &CacheOptions) -> Result<()> {
+    match &options.command {
+        CacheCommand::Update(update_options) => update(app, update_options),
+    }
+}
+
+/// Create and update the mirrors for the trees matched by the queries.
+fn update(app: &mut model::ApplicationContext, options: &CacheUpdateOptions) -> Result<()> {
+    let quiet = app.options.quiet;
+    let verbose = app.options.verbose;
+    let config = app.get_root_config();
+    let cache = config.get_cache_path().ok_or_else(|| {
+        errors::GardenError::ConfigurationError(string!("garden.cache is not configured"))
+    })?;
+
+    // Gather the unique "origin" URLs for the matched trees.
+    let mut contexts = Vec::new();
+    if options.queries.is_empty() {
+        for tree in config.trees.keys() {
+            contexts.push(model::TreeContext::new(tree, config.get_id(), None, None));
+        }
+    }
+    for query in &options.queries {
+        contexts.append(&mut query::resolve_trees(config, query));
+    }
+    let mut mirrors: Vec<(String, String)> = Vec::new();
+    let mut urls = HashSet::new();
+    for ctx in &contexts {
+        let tree = match config.trees.get(&ctx.tree) {
+            Some(tree) if !tree.is_symlink => tree,
+            _ => continue,
+        };
+        if let Some(remote) = tree.remotes.get("origin") {
+            let url = eval::tree_value(config, remote.get_expr(), &ctx.tree, ctx.garden.as_ref());
+            if !url.is_empty() && urls.insert(url.clone()) {
+                let mirror = mirror_path(&cache, &url).to_string_lossy().to_string();
+                mirrors.push((url, mirror));
+            }
+        }
+    }
+
+    cmd::initialize_threads(options.num_jobs);
+    let statuses: Vec<i32> = mirrors
+        .par_iter()
+        .map(|(url, mirror)| {
+            if !quiet {
+                eprintln!(
+                    "{} {}",
+                    model::Color::cyan("#"),
+                    model::Color::blue(url).bold()
+                );
+            }
+            update_mirror(url, mirror, verbose)
+        })
+        .collect();
+
+    // Return the last non-zero exit status.
+    let exit_status = statuses
+        .into_iter()
+        .rev()
+        .find(|status| *status != errors::EX_OK)
+        .unwrap_or(errors::EX_OK);
+
+    cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
+}
+
+/// Return the path to the bare mirror for a remote URL.
+/// Mirrors are stored in the cache directory using the URL's host and path,
+/// e.g. "https://github.com/garden-rs/garden" maps to "github.com/garden-rs/garden.git".
+pub fn mirror_path(cache: &std::path::Path, url: &str) -> std::path::PathBuf {
+    // Remove the "scheme://" and "user@" prefixes.
+    let location = match url.split_once("://") {
+        Some((_, location)) => location,
+        None => url,
+    };
+    let location = match location.split_once('@') {
+        Some((user, location)) if !user.contains('/') => location,
+        _ => location,
+    };
+    let mut components: Vec<&str> = location
+        .split(['/', ':'])
+        .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+        .collect();
+    let name = match components.pop() {
+        Some(name) => format!("{}.git", name.trim_end_matches(".git")),
+        None => string!("mirror.git"),
+    };
+
+    let mut path = cache.to_path_buf();
+    path.extend(components);
+    path.push(name);
+
+    path
+}
+
+/// Create a bare mirror using "git clone --mirror" when it does not already exist.
+pub fn create_mirror(url: &str, mirror: &str, verbose: u8) -> i32 {
+    let mirror_path = std::path::Path::new(mirror);
+    if mirror_path.exists() {
+        return errors::EX_OK;
+    }
+    if let Some(parent) = mirror_path.parent() {
+        if std::fs::create_dir_all(parent).is_err() {
+            return errors::EX_CANTCREAT;
+        }
+    }
+    let command = ["git", "clone", "--mirror", "--quiet", url, mirror];
+    if verbose > 1 {
+        print_command(&command);
+    }
+    let exec = cmd::exec_cmd(&command);
+
+    cmd::status(exec.join())
+}
+
+/// Fetch updates into an existing mirror or create a new mirror.
+pub fn update_mirror(url: &str, mirror: &str, verbose: u8) -> i32 {
+    if !std::path::Path::new(mirror).exists() {
+        return create_mirror(url, mirror, verbose);
+    }
+    let command = ["git", "remote", "update", "--prune"];
+    if verbose > 1 {
+        print_command(&command);
+    }
+    let exec = cmd::exec_in_dir(&command, &mirror);
+
+    cmd::status(exec.join())
+}
+
+/// Print a command that will be executed.
+fn print_command(command: &[&str]) {
+    println!(
+        "{} {}",
+        model::Color::cyan(":"),
+        model::Color::green(cmd::quote_command(command))
+    );
+}
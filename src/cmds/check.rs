@@ -0,0 +1,578 @@
+use super::super::cmd;
+use super::super::errors;
+use super::super::eval;
+use super::super::git;
+use super::super::model;
+use super::super::model::Color;
+use super::super::query;
+use super::grow;
+
+use anyhow::Result;
+use clap::Parser;
+use std::collections::HashSet;
+
+/// Compare trees on disk against the configuration
+#[derive(Parser, Clone, Debug)]
+#[command(author, about, long_about)]
+pub struct CheckOptions {
+    /// Reconcile the differences between the trees and the configuration
+    #[arg(long)]
+    fix: bool,
+    /// Tree query for the gardens, groups or trees to check
+    queries: Vec<String>,
+}
+
+/// Drift is a difference between a tree on disk and its configuration.
+#[derive(Clone, Debug)]
+struct Drift {
+    /// Description of the difference.
+    message: String,
+    /// How the difference is reconciled when "--fix" is specified.
+    fix: Fix,
+}
+
+/// Fix describes how a Drift is reconciled.
+#[derive(Clone, Debug)]
+enum Fix {
+    /// The difference cannot be reconciled automatically.
+    Unfixable,
+    /// The tree is grown using "garden grow".
+    Grow,
+    /// A Git command is run in the tree.
+    Command(Vec<String>),
+    /// A gitconfig setting is synchronized with the configured values.
+    GitConfig { name: String, values: Vec<String> },
+    /// The symlink is replaced with a symlink to the configured target.
+    Symlink { target: String },
+}
+
+impl Drift {
+    fn new(message: String, fix: Fix) -> Self {
+        Drift { message, fix }
+    }
+}
+
+/// Main entry point for the "garden check" command
+pub fn main(app: &mut model::ApplicationContext, options: &CheckOptions) -> Result<()> {
+    let mut queries = options.queries.clone();
+    if queries.is_empty() {
+        queries.push(".".into());
+    }
+    if app.options.debug_level("check") > 0 {
+        debug!("queries: {:?}", queries);
+    }
+    let params = grow::GrowParams {
+        num_jobs: 1,
+        quiet: app.options.quiet,
+        verbose: app.options.verbose,
+        ..grow::GrowParams::default()
+    };
+    let config = app.get_root_config();
+    let exit_status = check(config, &params, options.fix, &queries)?;
+
+    cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
+}
+
+/// Check every tree in the evaluated tree queries and optionally reconcile the differences.
+/// Returns a non-zero exit status when differences remain.
+pub fn check(
+    config: &model::Configuration,
+    params: &grow::GrowParams,
+    fix: bool,
+    queries: &[String],
+) -> Result<i32> {
+    let mut remaining = 0;
+    let mut visited = HashSet::new();
+    // Repositories created using "git worktree" share a common Git configuration
+    // and only need to be checked once.
+    let mut configured_worktrees = HashSet::new();
+    for query in queries {
+        for ctx in &query::resolve_trees(config, query) {
+            let tree = match config.trees.get(&ctx.tree) {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let path = tree.path_as_ref()?.clone();
+            if !visited.insert(path.clone()) {
+                continue;
+            }
+            let is_configured =
+                !configured_worktrees.insert(query::shared_worktree_path(config, ctx));
+            let drifts = tree_drift(config, ctx, &path, is_configured)?;
+            if drifts.is_empty() {
+                if params.verbose > 0 {
+                    println!(
+                        "{} {}  {}",
+                        Color::green("+"),
+                        Color::green(&ctx.tree).bold(),
+                        Color::green(&path)
+                    );
+                }
+                continue;
+            }
+            for drift in &drifts {
+                let is_fixed = fix && fix_drift(config, ctx, &path, drift, params)?;
+                if is_fixed {
+                    println!(
+                        "{} {}: {} {}",
+                        Color::green("+"),
+                        Color::green(&ctx.tree).bold(),
+                        drift.message,
+                        Color::green("(fixed)")
+                    );
+                    continue;
+                }
+                remaining += 1;
+                let status = match (fix, &drift.fix) {
+                    (false, _) => String::new(),
+                    (true, Fix::Unfixable) => format!(" {}", Color::red("(not fixable)")),
+                    (true, _) => format!(" {}", Color::red("(unable to fix)")),
+                };
+                println!(
+                    "{} {}: {}{}",
+                    Color::red("!"),
+                    Color::blue(&ctx.tree).bold(),
+                    drift.message,
+                    status
+                );
+            }
+        }
+    }
+
+    match remaining {
+        0 => Ok(errors::EX_OK),
+        _ => Ok(errors::EX_ERROR),
+    }
+}
+
+/// Compare a tree on disk against its configuration.
+/// Remotes and gitconfig settings are not checked when "is_configured" is true.
+fn tree_drift(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    path: &str,
+    is_configured: bool,
+) -> Result<Vec<Drift>> {
+    let mut drifts = Vec::new();
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return Ok(drifts),
+    };
+    let pathbuf = std::path::PathBuf::from(path);
+    let is_symlink = std::fs::read_link(&pathbuf).is_ok();
+    if !is_symlink && !pathbuf.exists() {
+        // Trees without an "origin" remote cannot be cloned.
+        let can_grow = tree.is_symlink || tree.is_worktree || tree.remotes.contains_key("origin");
+        let drift = match can_grow {
+            true => Drift::new("tree is missing".into(), Fix::Grow),
+            false => Drift::new(
+                "tree is missing and has no \"origin\" remote".into(),
+                Fix::Unfixable,
+            ),
+        };
+        drifts.push(drift);
+        return Ok(drifts);
+    }
+    if tree.is_symlink {
+        let target = tree.symlink_as_ref()?;
+        match std::fs::read_link(&pathbuf) {
+            Ok(link) => {
+                let link_path = match pathbuf.parent() {
+                    Some(parent) => parent.join(&link),
+                    None => link.clone(),
+                };
+                if !is_same_path(&link_path, std::path::Path::new(target)) {
+                    drifts.push(Drift::new(
+                        format!(
+                            "symlink target is \"{}\", expected \"{}\"",
+                            link.to_string_lossy(),
+                            target
+                        ),
+                        Fix::Symlink {
+                            target: target.clone(),
+                        },
+                    ));
+                }
+            }
+            Err(_) => {
+                drifts.push(Drift::new(
+                    format!("path is not a symlink, expected a symlink to \"{target}\""),
+                    Fix::Unfixable,
+                ));
+            }
+        }
+        return Ok(drifts);
+    }
+    if is_symlink {
+        drifts.push(Drift::new(
+            "path is a symlink, expected a repository".into(),
+            Fix::Unfixable,
+        ));
+        return Ok(drifts);
+    }
+
+    // Trees that are not Git repositories cannot be checked any further.
+    let is_bare = match rev_parse_bool(path, "--is-bare-repository") {
+        Some(is_bare) => is_bare,
+        None => {
+            drifts.push(Drift::new(
+                "path is not a Git repository".into(),
+                Fix::Unfixable,
+            ));
+            return Ok(drifts);
+        }
+    };
+    if is_bare && !tree.is_bare_repository {
+        drifts.push(Drift::new(
+            "repository is bare, expected a non-bare repository".into(),
+            Fix::Unfixable,
+        ));
+    } else if !is_bare && tree.is_bare_repository {
+        drifts.push(Drift::new(
+            "repository is not bare, expected a bare repository".into(),
+            Fix::Unfixable,
+        ));
+    }
+
+    // Worktrees share the shallow state of their parent repository.
+    if tree.is_worktree {
+        worktree_drift(config, ctx, &pathbuf, &mut drifts)?;
+    } else {
+        let is_shallow = rev_parse_bool(path, "--is-shallow-repository").unwrap_or(false);
+        if is_shallow && tree.clone_depth == 0 {
+            drifts.push(Drift::new(
+                "repository is shallow, expected a full clone".into(),
+                Fix::Command(vec![
+                    string!("git"),
+                    string!("fetch"),
+                    string!("--unshallow"),
+                ]),
+            ));
+        } else if !is_shallow && tree.clone_depth > 0 {
+            drifts.push(Drift::new(
+                format!(
+                    "repository is not shallow, expected depth {}",
+                    tree.clone_depth
+                ),
+                Fix::Unfixable,
+            ));
+        }
+        if let Ok(details) = git::worktree_details(&pathbuf) {
+            if let model::GitTreeType::Worktree(parent) = details.tree_type {
+                drifts.push(Drift::new(
+                    format!("tree is a worktree of \"{parent}\", expected a repository"),
+                    Fix::Unfixable,
+                ));
+            }
+        }
+    }
+
+    if !is_bare {
+        checkout_drift(config, ctx, path, &mut drifts);
+    }
+    if is_configured {
+        return Ok(drifts);
+    }
+    remotes_drift(config, ctx, path, &mut drifts);
+    for (name, values) in eval::tree_gitconfig(config, ctx) {
+        let existing = gitconfig_values(path, &name);
+        if existing != values {
+            let expected = if values.is_empty() {
+                "unset".to_string()
+            } else {
+                format!("{values:?}")
+            };
+            drifts.push(Drift::new(
+                format!("gitconfig {name} is {existing:?}, expected {expected}"),
+                Fix::GitConfig { name, values },
+            ));
+        }
+    }
+
+    Ok(drifts)
+}
+
+/// Compare a worktree's parent repository against the configured parent tree.
+fn worktree_drift(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    pathbuf: &std::path::Path,
+    drifts: &mut Vec<Drift>,
+) -> Result<()> {
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return Ok(()),
+    };
+    let worktree = eval::tree_value(
+        config,
+        tree.worktree.get_expr(),
+        &ctx.tree,
+        ctx.garden.as_ref(),
+    );
+    let parent_path =
+        match query::tree_from_name(config, &worktree, ctx.garden.as_ref(), ctx.group.as_ref())
+            .and_then(|parent_ctx| config.trees.get(&parent_ctx.tree))
+        {
+            Some(parent) => parent.path_as_ref()?.clone(),
+            None => {
+                return Err(errors::GardenError::WorktreeNotFound {
+                    tree: ctx.tree.clone(),
+                    worktree,
+                }
+                .into())
+            }
+        };
+    let is_worktree_of_parent = match git::worktree_details(pathbuf) {
+        Ok(details) => match details.tree_type {
+            model::GitTreeType::Worktree(parent) => is_same_path(
+                std::path::Path::new(&parent),
+                std::path::Path::new(&parent_path),
+            ),
+            _ => false,
+        },
+        Err(_) => false,
+    };
+    if !is_worktree_of_parent {
+        drifts.push(Drift::new(
+            format!("tree is not a worktree of \"{worktree}\""),
+            Fix::Unfixable,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Compare the remotes in a repository against the configured remotes.
+/// Unconfigured remotes are only reported for "remotes-exclusive" trees.
+fn remotes_drift(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    path: &str,
+    drifts: &mut Vec<Drift>,
+) {
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return,
+    };
+    let mut existing_remotes = Vec::new();
+    let command = ["git", "config", "--get-regexp", r"^remote\..*\.url$"];
+    let exec = cmd::exec_in_dir(&command, path);
+    if let Ok(capture) = cmd::capture_stdout(exec) {
+        for line in cmd::trim_stdout(&capture).lines() {
+            if let Some((key, url)) = line.split_once(' ') {
+                if let Some(remote) = key
+                    .strip_prefix("remote.")
+                    .and_then(|key| key.strip_suffix(".url"))
+                {
+                    existing_remotes.push((remote.to_string(), url.to_string()));
+                }
+            }
+        }
+    }
+
+    for (remote, var) in &tree.remotes {
+        let url = eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref());
+        match existing_remotes.iter().find(|(name, _)| name == remote) {
+            Some((_, existing_url)) if *existing_url == url => (),
+            Some((_, existing_url)) => {
+                drifts.push(Drift::new(
+                    format!("remote {remote} url is \"{existing_url}\", expected \"{url}\""),
+                    Fix::Command(vec![
+                        string!("git"),
+                        string!("remote"),
+                        string!("set-url"),
+                        remote.clone(),
+                        url,
+                    ]),
+                ));
+            }
+            None => {
+                drifts.push(Drift::new(
+                    format!("remote {remote} is missing"),
+                    Fix::Command(vec![
+                        string!("git"),
+                        string!("remote"),
+                        string!("add"),
+                        remote.clone(),
+                        url,
+                    ]),
+                ));
+            }
+        }
+    }
+
+    if !tree.is_remotes_exclusive || tree.remotes.is_empty() {
+        return;
+    }
+    let remotes_keep: Vec<glob::Pattern> = tree
+        .remotes_keep
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+        .collect();
+    let mut stale_remotes: Vec<&String> = existing_remotes
+        .iter()
+        .map(|(remote, _)| remote)
+        .filter(|remote| !tree.remotes.contains_key(*remote))
+        .filter(|remote| !remotes_keep.iter().any(|keep| keep.matches(remote)))
+        .collect();
+    stale_remotes.sort();
+    for remote in stale_remotes {
+        drifts.push(Drift::new(
+            format!("remote {remote} is not configured"),
+            Fix::Command(vec![
+                string!("git"),
+                string!("remote"),
+                string!("remove"),
+                remote.clone(),
+            ]),
+        ));
+    }
+}
+
+/// Compare the checked out branch or revision against the configured branch or pin.
+fn checkout_drift(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    path: &str,
+    drifts: &mut Vec<Drift>,
+) {
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return,
+    };
+    if let Some(pin) = eval::tree_pin(config, &ctx.tree, ctx.garden.as_ref()) {
+        let revision = pin.revision();
+        let commit = match git::rev_parse_commit(path, &revision) {
+            Some(commit) => commit,
+            None => {
+                drifts.push(Drift::new(
+                    format!("unable to resolve {pin}"),
+                    Fix::Unfixable,
+                ));
+                return;
+            }
+        };
+        let head = git::rev_parse_commit(path, "HEAD").unwrap_or_default();
+        if head != commit {
+            let mut command = vec![string!("git"), string!("checkout"), string!("--quiet")];
+            if !matches!(pin, model::TreePin::Ref(_)) {
+                command.push(string!("--detach"));
+            }
+            command.push(revision);
+            drifts.push(Drift::new(
+                format!("HEAD is at {}, expected {pin}", short_commit(&head)),
+                Fix::Command(command),
+            ));
+        }
+        return;
+    }
+
+    let branch = eval::tree_value(
+        config,
+        tree.branch.get_expr(),
+        &ctx.tree,
+        ctx.garden.as_ref(),
+    );
+    if branch.is_empty() {
+        return;
+    }
+    let message = match git::current_branch(path) {
+        Some(current_branch) if current_branch == branch => return,
+        Some(current_branch) => {
+            format!("branch {current_branch} is checked out, expected {branch}")
+        }
+        None => format!("HEAD is detached, expected branch {branch}"),
+    };
+    drifts.push(Drift::new(
+        message,
+        Fix::Command(vec![
+            string!("git"),
+            string!("checkout"),
+            string!("--quiet"),
+            branch,
+        ]),
+    ));
+}
+
+/// Reconcile a difference. Returns true when the difference was reconciled.
+fn fix_drift(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    path: &str,
+    drift: &Drift,
+    params: &grow::GrowParams,
+) -> Result<bool> {
+    let is_fixed = match &drift.fix {
+        Fix::Unfixable => false,
+        // Trees are grown using their resolved context so that garden settings apply.
+        // The tree is only fixed when the path exists after it has been grown.
+        Fix::Grow => {
+            let pathbuf = std::path::PathBuf::from(path);
+            grow::grow_contexts(config, std::slice::from_ref(ctx), params)? == errors::EX_OK
+                && (std::fs::read_link(&pathbuf).is_ok() || pathbuf.exists())
+        }
+        Fix::Command(command) => {
+            if params.verbose > 1 {
+                println!(
+                    "{} {}",
+                    Color::cyan(":"),
+                    Color::green(cmd::quote_command(command))
+                );
+            }
+            let exec = cmd::exec_in_dir(command, path);
+            cmd::status(exec.join()) == errors::EX_OK
+        }
+        Fix::GitConfig { name, values } => {
+            let pathbuf = std::path::PathBuf::from(path);
+            grow::update_gitconfig(&pathbuf, name, values, params.verbose) == errors::EX_OK
+        }
+        Fix::Symlink { target } => {
+            let pathbuf = std::path::PathBuf::from(path);
+            std::fs::remove_file(&pathbuf)?;
+            grow::create_symlink(&pathbuf, target)?;
+            true
+        }
+    };
+
+    Ok(is_fixed)
+}
+
+/// Evaluate a boolean "git rev-parse" option, e.g. "--is-bare-repository".
+/// None is returned when the path is not a Git repository.
+fn rev_parse_bool(path: &str, option: &str) -> Option<bool> {
+    let command = ["git", "rev-parse", option];
+    let exec = cmd::exec_in_dir(&command, path);
+    match cmd::capture(exec) {
+        Ok(capture) if capture.success() => Some(cmd::trim_stdout(&capture) == "true"),
+        _ => None,
+    }
+}
+
+/// Return the values of a setting from the repository's Git configuration.
+fn gitconfig_values(path: &str, name: &str) -> Vec<String> {
+    let command = ["git", "config", "--local", "--get-all", name];
+    let exec = cmd::exec_in_dir(&command, path);
+    match cmd::capture_stdout(exec) {
+        Ok(capture) if capture.success() => cmd::trim_stdout(&capture)
+            .lines()
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Return true when both paths refer to the same location.
+fn is_same_path(path: &std::path::Path, other: &std::path::Path) -> bool {
+    match (std::fs::canonicalize(path), std::fs::canonicalize(other)) {
+        (Ok(path), Ok(other)) => path == other,
+        _ => path == other,
+    }
+}
+
+/// Abbreviate a commit ID for display.
+fn short_commit(commit: &str) -> &str {
+    match commit.get(..8) {
+        Some(short) => short,
+        None => commit,
+    }
+}
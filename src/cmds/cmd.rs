@@ -4,10 +4,17 @@ use super::super::errors;
 use super::super::eval;
 use super::super::model;
 use super::super::query;
+use super::super::report;
 
 use anyhow::Result;
 use clap;
 use clap::{CommandFactory, FromArgMatches, Parser};
+use indexmap::IndexSet;
+use rayon::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Run one or more custom commands over a tree query
 #[derive(Parser, Clone, Debug)]
@@ -16,6 +23,9 @@ pub struct CmdOptions {
     /// Run a command in all trees before running the next command
     #[arg(long, short)]
     breadth_first: bool,
+    /// Number of trees to run commands in parallel. "0" uses all available cores
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    num_jobs: usize,
     /// Continue to the next tree when errors occur
     #[arg(long, short)]
     keep_going: bool,
@@ -28,12 +38,40 @@ pub struct CmdOptions {
     /// returns a non-zero exit code.
     #[arg(long, short)]
     no_errexit: bool,
+    /// Print the commands that would be run without running them
+    #[arg(long, short = 'N')]
+    dry_run: bool,
+    /// Display command output directly, prefixed by tree name, or in per-tree blocks
+    #[arg(long, value_enum, default_value_t = model::OutputMode::Inherit)]
+    output: model::OutputMode,
+    /// Kill commands that run longer than the specified number of seconds
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+    /// Retry failed commands up to the specified number of times
+    #[arg(long)]
+    retries: Option<u64>,
+    /// Number of seconds to wait before retrying a failed command
+    #[arg(long, value_name = "SECONDS")]
+    retry_delay: Option<u64>,
+    /// Print a summary of each command's exit status and duration when finished
+    #[arg(long)]
+    summary: bool,
+    /// Write the exit status and duration of each command to a report file
+    #[arg(long, value_name = "FILE")]
+    report: Option<std::path::PathBuf>,
+    /// File format used by "--report"
+    #[arg(long, value_enum, default_value_t = model::ReportFormat::Json)]
+    report_format: model::ReportFormat,
+    /// Re-run only the trees and commands that failed during the previous run
+    #[arg(long, visible_alias = "rerun-failed")]
+    failed: bool,
     /// Tree query for the gardens, groups or trees to execute commands within
-    query: String,
+    #[arg(required_unless_present = "failed")]
+    query: Option<String>,
     /// Custom commands to run over the resolved trees
     // NOTE: value_terminator may not be needed in future versions of clap_complete.
     // https://github.com/clap-rs/clap/pull/4612
-    #[arg(required = true, value_terminator = "--")]
+    #[arg(required_unless_present = "failed", value_terminator = "--")]
     commands: Vec<String>,
     /// Arguments to forward to custom commands
     #[arg(last = true)]
@@ -44,6 +82,9 @@ pub struct CmdOptions {
 #[derive(Parser, Clone, Debug)]
 #[command(bin_name = "garden")]
 pub struct CustomOptions {
+    /// Number of trees to run commands in parallel. "0" uses all available cores
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    num_jobs: usize,
     /// Continue to the next tree when errors occur
     #[arg(long, short)]
     keep_going: bool,
@@ -56,6 +97,33 @@ pub struct CustomOptions {
     /// returns a non-zero exit code.
     #[arg(long, short)]
     no_errexit: bool,
+    /// Print the commands that would be run without running them
+    #[arg(long, short = 'N')]
+    dry_run: bool,
+    /// Display command output directly, prefixed by tree name, or in per-tree blocks
+    #[arg(long, value_enum, default_value_t = model::OutputMode::Inherit)]
+    output: model::OutputMode,
+    /// Kill commands that run longer than the specified number of seconds
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+    /// Retry failed commands up to the specified number of times
+    #[arg(long)]
+    retries: Option<u64>,
+    /// Number of seconds to wait before retrying a failed command
+    #[arg(long, value_name = "SECONDS")]
+    retry_delay: Option<u64>,
+    /// Print a summary of each command's exit status and duration when finished
+    #[arg(long)]
+    summary: bool,
+    /// Write the exit status and duration of each command to a report file
+    #[arg(long, value_name = "FILE")]
+    report: Option<std::path::PathBuf>,
+    /// File format used by "--report"
+    #[arg(long, value_enum, default_value_t = model::ReportFormat::Json)]
+    report_format: model::ReportFormat,
+    /// Re-run only the trees and commands that failed during the previous run
+    #[arg(long, visible_alias = "rerun-failed")]
+    failed: bool,
     /// Tree queries for the Gardens/Groups/Trees to execute commands within
     // NOTE: value_terminator may not be needed in future versions of clap_complete.
     // https://github.com/clap-rs/clap/pull/4612
@@ -69,13 +137,16 @@ pub struct CustomOptions {
 /// Main entry point for `garden cmd <query> <command>...`.
 pub fn main_cmd(app: &mut model::ApplicationContext, options: &CmdOptions) -> Result<()> {
     if app.options.debug_level("cmd") > 0 {
-        debug!("query: {}", options.query);
+        debug!("query: {:?}", options.query);
         debug!("commands: {:?}", options.commands);
         debug!("arguments: {:?}", options.arguments);
     }
-    let params = CmdParams::from_cmd_options(options);
-    let exit_status = cmd(app, &options.query, &params)?;
-    cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
+    apply_parameter_defaults(app, &options.commands);
+    let mut params = CmdParams::from_cmd_options(options);
+    if options.failed && !select_failed(app, &mut params)? {
+        return Ok(());
+    }
+    cmds(app, &params)
 }
 
 /// CmdParams are used to control the execution of run_cmd_vec().
@@ -89,24 +160,62 @@ pub struct CmdParams {
     breadth_first: bool,
     keep_going: bool,
     exit_on_error: bool,
+    num_jobs: usize,
+    output: model::OutputMode,
+    dry_run: bool,
+    timeout: Option<u64>,
+    retries: Option<u64>,
+    retry_delay: Option<u64>,
+    summary: bool,
+    report: Option<std::path::PathBuf>,
+    report_format: model::ReportFormat,
+    /// Commands from the previous run that are being re-run by "--failed".
+    failed: Option<Vec<report::CommandResult>>,
 }
 
 impl CmdParams {
     pub fn new() -> Self {
         Self {
             exit_on_error: true,
+            num_jobs: 1,
             ..CmdParams::default()
         }
     }
 
+    /// Return true when a command should run in the specified tree context.
+    /// Only the commands that failed during the previous run are selected by "--failed".
+    fn is_selected(&self, query: &str, context: &model::TreeContext, name: &str) -> bool {
+        match &self.failed {
+            Some(failed) => failed.iter().any(|result| {
+                result.query == query
+                    && result.tree == context.tree
+                    && result.garden == context.garden
+                    && result.command == name
+            }),
+            None => true,
+        }
+    }
+
     /// Build CmdParams from a CmdOptions struct
     pub fn from_cmd_options(options: &CmdOptions) -> Self {
         let mut params = Self::new();
         params.commands = options.commands.clone();
         params.arguments = options.arguments.clone();
+        if let Some(query) = &options.query {
+            params.queries.push(query.clone());
+        }
         params.breadth_first = options.breadth_first;
         params.exit_on_error = !options.no_errexit;
         params.keep_going = options.keep_going;
+        params.num_jobs = options.num_jobs;
+        params.output = options.output;
+        params.dry_run = options.dry_run;
+        params.timeout = options.timeout;
+        params.retries = options.retries;
+        params.retry_delay = options.retry_delay;
+        params.summary = options.summary;
+        params.report = options.report.clone();
+        params.report_format = options.report_format;
 
         params
     }
@@ -118,7 +227,8 @@ impl CmdParams {
         params.arguments = options.arguments.clone();
         params.queries = options.queries.clone();
         // Default to "." when no queries have been specified.
-        if params.queries.is_empty() {
+        // "--failed" re-runs the previous queries when no queries have been specified.
+        if params.queries.is_empty() && !options.failed {
             params.queries.push(".".into());
         }
 
@@ -129,6 +239,15 @@ impl CmdParams {
         params.breadth_first = true;
         params.keep_going = options.keep_going;
         params.exit_on_error = !options.no_errexit;
+        params.num_jobs = options.num_jobs;
+        params.output = options.output;
+        params.dry_run = options.dry_run;
+        params.timeout = options.timeout;
+        params.retries = options.retries;
+        params.retry_delay = options.retry_delay;
+        params.summary = options.summary;
+        params.report = options.report.clone();
+        params.report_format = options.report_format;
 
         params
     }
@@ -145,10 +264,20 @@ pub fn main_custom(app: &mut model::ApplicationContext, arguments: &Vec<String>)
     // Set the command name to "garden <custom>".
     let name = &arguments[0];
     let garden_custom = format!("garden {name}");
-    let cli = CustomOptions::command().bin_name(garden_custom);
+    let mut cli = CustomOptions::command().bin_name(garden_custom);
+    let mut parameters = Vec::new();
+    // Display the command's description in "garden <custom> --help".
+    if let Some(settings) = app.get_root_config().command_settings.get(name) {
+        if !settings.description.is_empty() {
+            cli = cli.about(settings.description.clone());
+        }
+        parameters = settings.parameters.clone();
+    }
+    cli = add_parameter_args(cli, &parameters)?;
     let matches = cli.get_matches_from(arguments);
     let options = <CustomOptions as FromArgMatches>::from_arg_matches(&matches)
         .map_err(format_error::<CustomOptions>)?;
+    apply_parameters(app, &parameters, &matches);
 
     if app.options.debug_level("cmd") > 0 {
         debug!("command: {}", name);
@@ -159,10 +288,131 @@ pub fn main_custom(app: &mut model::ApplicationContext, arguments: &Vec<String>)
     let mut params = CmdParams::from_custom_options(&options);
     // Add the custom command name to the list of commands. cmds() operates on a vec of commands.
     params.commands.push(name.to_string());
+    if options.failed && !select_failed(app, &mut params)? {
+        return Ok(());
+    }
 
     cmds(app, &params)
 }
 
+/// Return the clap argument ID used for a command parameter.
+fn parameter_id(parameter: &model::CommandParameter) -> String {
+    format!("param:{}", parameter.name)
+}
+
+/// Add options for the parameters declared by a custom command.
+/// Parameters cannot shadow the options provided by garden itself.
+pub fn add_parameter_args(
+    mut cli: clap::Command,
+    parameters: &[model::CommandParameter],
+) -> Result<clap::Command, errors::GardenError> {
+    for parameter in parameters {
+        let conflict = cli.get_arguments().any(|arg| {
+            arg.get_long() == Some(parameter.name.as_str())
+                || arg
+                    .get_visible_aliases()
+                    .is_some_and(|aliases| aliases.contains(&parameter.name.as_str()))
+                || (parameter.short.is_some() && arg.get_short() == parameter.short)
+        });
+        if conflict || matches!(parameter.short, Some('h')) || parameter.name == "help" {
+            return Err(errors::GardenError::ConfigurationError(format!(
+                "parameter conflicts with a builtin option: {}",
+                parameter.name
+            )));
+        }
+        let mut arg = clap::Arg::new(parameter_id(parameter))
+            .long(parameter.name.clone())
+            .help(parameter.help.clone());
+        if let Some(short) = parameter.short {
+            arg = arg.short(short);
+        }
+        arg = match parameter.kind {
+            model::ParameterType::Bool => arg.action(clap::ArgAction::SetTrue),
+            model::ParameterType::Integer => arg
+                .value_name("INTEGER")
+                .value_parser(clap::value_parser!(i64)),
+            model::ParameterType::String => arg.value_name("VALUE"),
+        };
+        if parameter.kind != model::ParameterType::Bool {
+            if let Some(default) = &parameter.default {
+                arg = arg.default_value(default.clone());
+            }
+        }
+        cli = cli.arg(arg);
+    }
+
+    Ok(cli)
+}
+
+/// Expose parameter values to commands as variables.
+/// Default values do not override variables defined using "garden -D name=value".
+fn apply_parameters(
+    app: &mut model::ApplicationContext,
+    parameters: &[model::CommandParameter],
+    matches: &clap::ArgMatches,
+) {
+    let config = app.get_root_config_mut();
+    for parameter in parameters {
+        let id = parameter_id(parameter);
+        let is_default = !matches!(
+            matches.value_source(&id),
+            Some(clap::parser::ValueSource::CommandLine)
+        );
+        if is_default && config.variables.contains_key(&parameter.name) {
+            continue;
+        }
+        let value = match parameter.kind {
+            model::ParameterType::Bool => {
+                if matches.get_flag(&id) {
+                    string!("true")
+                } else {
+                    parameter
+                        .default
+                        .clone()
+                        .unwrap_or_else(|| string!("false"))
+                }
+            }
+            model::ParameterType::Integer => match matches.get_one::<i64>(&id) {
+                Some(value) => value.to_string(),
+                None => string!(""),
+            },
+            model::ParameterType::String => match matches.get_one::<String>(&id) {
+                Some(value) => value.clone(),
+                None => string!(""),
+            },
+        };
+        config
+            .variables
+            .insert(parameter.name.clone(), model::Variable::new(value, None));
+    }
+}
+
+/// Define variables for the default values of the parameters declared by commands.
+/// "garden cmd" does not accept parameter options so only the defaults are available.
+fn apply_parameter_defaults(app: &mut model::ApplicationContext, commands: &[String]) {
+    let config = app.get_root_config_mut();
+    let mut defaults = Vec::new();
+    for name in commands {
+        let Some(settings) = config.command_settings.get(name) else {
+            continue;
+        };
+        for parameter in &settings.parameters {
+            let default = match (&parameter.default, parameter.kind) {
+                (Some(default), _) => default.clone(),
+                (None, model::ParameterType::Bool) => string!("false"),
+                (None, _) => string!(""),
+            };
+            defaults.push((parameter.name.clone(), default));
+        }
+    }
+    for (name, default) in defaults {
+        config
+            .variables
+            .entry(name)
+            .or_insert_with(|| model::Variable::new(default, None));
+    }
+}
+
 /// Strategy: resolve the trees down to a set of tree indexes paired with an
 /// an optional garden context.
 ///
@@ -173,74 +423,75 @@ pub fn main_custom(app: &mut model::ApplicationContext, arguments: &Vec<String>)
 /// If the names resolve to trees, each tree is processed independently
 /// with no garden context.
 
-pub fn cmd(app: &mut model::ApplicationContext, query: &str, params: &CmdParams) -> Result<i32> {
+/// The outcome of each command is appended to "results".
+pub fn cmd(
+    app: &mut model::ApplicationContext,
+    query: &str,
+    params: &CmdParams,
+    results: &mut Vec<report::CommandResult>,
+) -> Result<i32> {
     // Mutable scope for app.get_root_config_mut()
     let config = app.get_root_config_mut();
     // Resolve the tree query into a vector of tree contexts.
     let contexts = query::resolve_trees(config, query);
 
-    if params.breadth_first {
-        run_cmd_breadth_first(app, &contexts, params)
+    let start = results.len();
+    let exit_status = if params.breadth_first {
+        run_cmd_breadth_first(app, query, &contexts, params, results)
     } else {
-        run_cmd_depth_first(app, &contexts, params)
+        run_cmd_depth_first(app, query, &contexts, params, results)
+    };
+    for result in &mut results[start..] {
+        result.query = query.to_string();
     }
+
+    exit_status
 }
 
 pub fn run_cmd_breadth_first(
     app: &mut model::ApplicationContext,
+    query: &str,
     contexts: &[model::TreeContext],
     params: &CmdParams,
+    results: &mut Vec<report::CommandResult>,
 ) -> Result<i32> {
     let mut exit_status: i32 = errors::EX_OK;
-    let quiet = app.options.quiet;
-    let verbose = app.options.verbose;
-    let shell = {
-        let config = app.get_root_config();
-        config.shell.to_string()
-    };
+    // Dependencies run once per tree across all of the commands.
+    let mut done: HashMap<(String, Option<String>), HashSet<String>> = HashMap::new();
     // Loop over each command, evaluate the tree environment,
     // and run the command in each context.
     for name in &params.commands {
+        let names = [name.to_string()];
         // One invocation runs multiple commands
+        let mut jobs = Vec::new();
         for context in contexts {
-            // Skip symlink trees.
-            let config = app.get_root_config();
-            let tree = match config.trees.get(&context.tree) {
-                Some(tree) => tree,
-                None => continue,
-            };
-            if tree.is_symlink {
+            if !params.is_selected(query, context, name) {
                 continue;
             }
-            // Evaluate the tree environment
-            let env = eval::environment(app.get_root_config(), context);
-
-            // Run each command in the tree's context
-            let path = tree.path_as_ref()?.to_string();
-            // Sparse gardens/missing trees are ok -> skip these entries.
-            if !model::print_tree(tree, verbose, quiet) {
-                continue;
+            let key = (context.tree.clone(), context.garden.clone());
+            let tree_done = done.entry(key).or_default();
+            if let Some(job) = tree_job(app, context, &names, params, tree_done)? {
+                // Evaluate jobs as we go when running sequentially.
+                if params.num_jobs == 1 {
+                    let status = run_tree_job(&app.options, &job, params, results);
+                    if status != errors::EX_OK {
+                        exit_status = status;
+                        if !params.keep_going {
+                            return Ok(status);
+                        }
+                    }
+                } else {
+                    jobs.push(job);
+                }
             }
-
-            // One command maps to multiple command sequences.
-            // When the scope is tree, only the tree's commands
-            // are included.  When the scope includes a gardens,
-            // its matching commands are appended to the end.
-            let cmd_seq_vec = eval::command(app, context, name);
-            app.get_root_config_mut().reset();
-
-            if let Err(cmd_status) = run_cmd_vec(
-                &app.options,
-                &path,
-                &shell,
-                &env,
-                &cmd_seq_vec,
-                &params.arguments,
-                params.exit_on_error,
-            ) {
-                exit_status = cmd_status;
+        }
+        // All trees must complete before running the next command.
+        if !jobs.is_empty() {
+            let status = run_tree_jobs_parallel(&app.options, &jobs, params, results);
+            if status != errors::EX_OK {
+                exit_status = status;
                 if !params.keep_going {
-                    return Ok(cmd_status);
+                    return Ok(status);
                 }
             }
         }
@@ -252,111 +503,479 @@ pub fn run_cmd_breadth_first(
 
 pub fn run_cmd_depth_first(
     app: &mut model::ApplicationContext,
+    query: &str,
     contexts: &[model::TreeContext],
     params: &CmdParams,
+    results: &mut Vec<report::CommandResult>,
 ) -> Result<i32> {
     let mut exit_status: i32 = errors::EX_OK;
-    let quiet = app.options.quiet;
-    let verbose = app.options.verbose;
-    let shell = {
-        let config = app.get_root_config();
-        config.shell.to_string()
-    };
+    let mut jobs = Vec::new();
     // Loop over each context, evaluate the tree environment and run the command.
     for context in contexts {
-        // Skip symlink trees.
-        let config = app.get_root_config();
-        let tree = match config.trees.get(&context.tree) {
-            Some(tree) => tree,
-            None => continue,
-        };
-        if tree.is_symlink {
+        let names: Vec<String> = params
+            .commands
+            .iter()
+            .filter(|name| params.is_selected(query, context, name))
+            .cloned()
+            .collect();
+        if names.is_empty() {
             continue;
         }
-        // Evaluate the tree environment
-        let env = eval::environment(app.get_root_config(), context);
+        let mut done = HashSet::new();
+        if let Some(job) = tree_job(app, context, &names, params, &mut done)? {
+            // Evaluate jobs as we go when running sequentially.
+            if params.num_jobs == 1 {
+                let status = run_tree_job(&app.options, &job, params, results);
+                if status != errors::EX_OK {
+                    exit_status = status;
+                    if !params.keep_going {
+                        return Ok(status);
+                    }
+                }
+            } else {
+                jobs.push(job);
+            }
+        }
+    }
+    if !jobs.is_empty() {
+        exit_status = run_tree_jobs_parallel(&app.options, &jobs, params, results);
+    }
+
+    // Return the last non-zero exit status.
+    Ok(exit_status)
+}
 
-        // Run each command in the tree's context
-        let path = tree.path_as_ref()?.to_string();
+/// TreeJob holds the evaluated environment and command strings for one tree context.
+/// Jobs are evaluated up front so that they can be run in parallel. Configuration
+/// variables are not thread-safe so evaluation must happen on the main thread.
+struct TreeJob {
+    /// Name of the tree.
+    name: String,
+    /// Optional garden context for the tree.
+    garden: Option<String>,
+    /// Heading printed before the commands are run.
+    header: String,
+    /// Working directory for the commands.
+    path: String,
+    /// Shell used to run the command strings.
+    shell: String,
+    /// Evaluated environment variables.
+    env: Vec<(String, String)>,
+    /// Commands to run in order, including dependencies and hooks.
+    commands: Vec<JobCommand>,
+}
 
-        // Sparse gardens/missing trees are ok -> skip these entries.
-        if !model::print_tree(tree, verbose, quiet) {
-            continue;
+/// JobCommand is a named command scheduled to run in a TreeJob.
+struct JobCommand {
+    /// Name of the command.
+    name: String,
+    /// Evaluated commands returned by eval::commands().
+    commands: Vec<model::EvaluatedCommand>,
+    /// Commands that must succeed before this command can run.
+    requires: Vec<String>,
+}
+
+/// Schedule holds the state used when expanding commands into JobCommands.
+struct Schedule<'a> {
+    context: &'a model::TreeContext,
+    params: &'a CmdParams,
+    /// Commands that have been scheduled in the tree.
+    done: &'a mut HashSet<String>,
+    /// Commands that are being expanded. Used to detect cycles.
+    visiting: Vec<String>,
+    /// Commands scheduled so far.
+    commands: Vec<JobCommand>,
+    /// True when a command was skipped by its "when" predicate.
+    skipped: bool,
+}
+
+/// Schedule a command along with its dependencies and pre/post hooks.
+/// Dependencies that have already been scheduled in the tree are not scheduled again.
+/// Hooks run every time the command runs.
+/// "kind" is "depends", "pre" or "post" when scheduling a dependency or hook and None
+/// when scheduling a command that was requested on the command-line.
+fn schedule_command(
+    app: &mut model::ApplicationContext,
+    schedule: &mut Schedule,
+    name: &str,
+    requires: &[String],
+    kind: Option<&str>,
+) -> Result<()> {
+    if kind == Some("depends") && schedule.done.contains(name) {
+        return Ok(());
+    }
+    if let Some(idx) = schedule
+        .visiting
+        .iter()
+        .position(|visiting| visiting == name)
+    {
+        let mut cycle = schedule.visiting[idx..].to_vec();
+        cycle.push(name.to_string());
+        return Err(errors::GardenError::ConfigurationError(format!(
+            "command dependency cycle: {}",
+            cycle.join(" -> ")
+        ))
+        .into());
+    }
+
+    let mut evaluated = eval::commands(app, schedule.context, name);
+    app.get_root_config_mut().reset();
+    let num_commands = evaluated.len();
+    // Dependencies and hooks must name commands that exist.
+    if let Some(kind) = kind {
+        if num_commands == 0 {
+            return Err(errors::GardenError::ConfigurationError(format!(
+                "unknown command in {kind}: {name}"
+            ))
+            .into());
         }
+    }
+    // Commands whose "when" predicate is not satisfied are skipped silently.
+    evaluated.retain(|command| !command.skip);
+    if num_commands > 0 && evaluated.is_empty() {
+        schedule.skipped = true;
+        schedule.done.insert(name.to_string());
+        return Ok(());
+    }
 
-        // One invocation runs multiple commands
-        for name in &params.commands {
-            // One command maps to multiple command sequences.
-            // When the scope is tree, only the tree's commands
-            // are included.  When the scope includes a gardens,
-            // its matching commands are appended to the end.
-            let cmd_seq_vec = eval::command(app, context, name);
-            app.get_root_config_mut().reset();
-
-            if let Err(cmd_status) = run_cmd_vec(
-                &app.options,
-                &path,
-                &shell,
-                &env,
-                &cmd_seq_vec,
-                &params.arguments,
-                params.exit_on_error,
-            ) {
-                exit_status = cmd_status;
-                if !params.keep_going {
-                    return Ok(cmd_status);
-                }
+    let mut depends = IndexSet::new();
+    let mut pre = IndexSet::new();
+    let mut post = IndexSet::new();
+    for command in &evaluated {
+        depends.extend(command.depends.iter().cloned());
+        pre.extend(command.pre.iter().cloned());
+        post.extend(command.post.iter().cloned());
+    }
+
+    schedule.visiting.push(name.to_string());
+    for dependency in &depends {
+        schedule_command(app, schedule, dependency, &[], Some("depends"))?;
+    }
+    let mut command_requires = requires.to_vec();
+    command_requires.extend(depends);
+    for hook in &pre {
+        schedule_command(app, schedule, hook, &command_requires, Some("pre"))?;
+    }
+    command_requires.extend(pre);
+
+    // Commands with "confirm: true" prompt before running. Prompts happen here,
+    // on the main thread, so that they are not interleaved when running in parallel.
+    // Declined commands are skipped without affecting the exit status.
+    if !schedule.params.dry_run {
+        let tree = &schedule.context.tree;
+        evaluated.retain(|command| !command.confirm || confirm_command(&command.name, tree));
+    }
+    schedule.commands.push(JobCommand {
+        name: name.to_string(),
+        commands: evaluated,
+        requires: command_requires,
+    });
+    schedule.done.insert(name.to_string());
+
+    let post_requires = [name.to_string()];
+    for hook in &post {
+        schedule_command(app, schedule, hook, &post_requires, Some("post"))?;
+    }
+    schedule.visiting.pop();
+
+    Ok(())
+}
+
+/// Evaluate the commands for a tree context into a TreeJob.
+/// Returns Ok(None) for symlink trees and for missing trees, which are skipped.
+/// Commands that have already run in the tree are recorded in "done" so that
+/// dependencies only run once per tree.
+fn tree_job(
+    app: &mut model::ApplicationContext,
+    context: &model::TreeContext,
+    names: &[String],
+    params: &CmdParams,
+    done: &mut HashSet<String>,
+) -> Result<Option<TreeJob>> {
+    let quiet = app.options.quiet;
+    let verbose = app.options.verbose;
+    let config = app.get_root_config();
+    let tree = match config.trees.get(&context.tree) {
+        Some(tree) => tree,
+        None => return Ok(None),
+    };
+    // Skip symlink trees.
+    if tree.is_symlink {
+        return Ok(None);
+    }
+    let path = tree.path_as_ref()?.to_string();
+    // Sparse gardens/missing trees are ok -> skip these entries.
+    if !std::path::PathBuf::from(&path).exists() {
+        if !quiet {
+            eprintln!("{}", model::display_missing_tree(tree, &path, verbose));
+        }
+        return Ok(None);
+    }
+    let header = model::display_tree(tree, &path, verbose);
+    let shell = config.shell.to_string();
+    // Evaluate the tree environment
+    let env = eval::environment(config, context);
+
+    // One command maps to multiple command sequences.
+    // When the scope is tree, only the tree's commands
+    // are included.  When the scope includes a gardens,
+    // its matching commands are appended to the end.
+    let mut schedule = Schedule {
+        context,
+        params,
+        done,
+        visiting: Vec::new(),
+        commands: Vec::new(),
+        skipped: false,
+    };
+    for name in names {
+        schedule_command(app, &mut schedule, name, &[], None)?;
+    }
+    let Schedule {
+        commands, skipped, ..
+    } = schedule;
+    // Trees where every command was skipped are omitted entirely so that no header is printed.
+    if skipped && commands.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(TreeJob {
+        name: context.tree.to_string(),
+        garden: context.garden.clone(),
+        header,
+        path,
+        shell,
+        env,
+        commands,
+    }))
+}
+
+/// Run all of the commands in a TreeJob and return the last non-zero exit status.
+/// The outcome of each command is appended to "results".
+fn run_tree_job(
+    options: &cli::MainOptions,
+    job: &TreeJob,
+    params: &CmdParams,
+    results: &mut Vec<report::CommandResult>,
+) -> i32 {
+    let mut exit_status = errors::EX_OK;
+    let is_block = params.output == model::OutputMode::Block;
+    // Buffered output is displayed along with the header once all commands complete.
+    if !options.quiet && !is_block {
+        eprintln!("{}", job.header);
+    }
+    let mut buffer = String::new();
+    // Dry-run mode displays the working directory and environment before the commands.
+    if params.dry_run {
+        for line in cmd::dry_run_preamble(&job.path, &job.env) {
+            cmd::output_line(
+                params.output,
+                &job.name,
+                &mut buffer,
+                &cmd::display_command(&line),
+            );
+        }
+    }
+    // Commands are not run when the commands that they require have failed.
+    // Skipped commands are recorded as failures so that "--failed" re-runs them.
+    // Without "--keep-going" the remaining commands are not run once a command fails
+    // but the commands that require the failed command are still recorded.
+    let mut failed = HashSet::new();
+    let mut stopped = false;
+    for job_command in &job.commands {
+        let name = &job_command.name;
+        if job_command
+            .requires
+            .iter()
+            .any(|required| failed.contains(required))
+        {
+            failed.insert(name);
+            if !params.dry_run {
+                results.push(report::CommandResult {
+                    tree: job.name.clone(),
+                    garden: job.garden.clone(),
+                    command: name.clone(),
+                    status: errors::EX_SKIPPED,
+                    ..Default::default()
+                });
             }
+            continue;
+        }
+        if stopped {
+            continue;
+        }
+        let start = std::time::Instant::now();
+        let status = run_cmd_vec(options, params, job, &job_command.commands, &mut buffer)
+            .err()
+            .unwrap_or(errors::EX_OK);
+        if !params.dry_run {
+            results.push(report::CommandResult {
+                tree: job.name.clone(),
+                garden: job.garden.clone(),
+                command: name.clone(),
+                status,
+                duration: start.elapsed(),
+                ..Default::default()
+            });
+        }
+        if status != errors::EX_OK {
+            failed.insert(name);
+            exit_status = status;
+            stopped = !params.keep_going;
         }
     }
+    if is_block {
+        let header = if options.quiet {
+            None
+        } else {
+            Some(job.header.as_str())
+        };
+        cmd::print_block(header, &buffer);
+    }
 
-    // Return the last non-zero exit status.
-    Ok(exit_status)
+    exit_status
+}
+
+/// Run TreeJobs in parallel using up to "params.num_jobs" threads.
+/// Without "--keep-going" no new jobs are started once a job fails and the first
+/// non-zero exit status is returned. The last non-zero exit status in query order
+/// is returned otherwise.
+fn run_tree_jobs_parallel(
+    options: &cli::MainOptions,
+    jobs: &[TreeJob],
+    params: &CmdParams,
+    results: &mut Vec<report::CommandResult>,
+) -> i32 {
+    cmd::initialize_threads(params.num_jobs);
+
+    let stop = AtomicBool::new(false);
+    let job_results: Vec<(i32, Vec<report::CommandResult>)> = jobs
+        .par_iter()
+        .map(|job| {
+            let mut job_results = Vec::new();
+            if stop.load(Ordering::SeqCst) {
+                return (errors::EX_OK, job_results);
+            }
+            let status = run_tree_job(options, job, params, &mut job_results);
+            if status != errors::EX_OK && !params.keep_going {
+                stop.store(true, Ordering::SeqCst);
+            }
+            (status, job_results)
+        })
+        .collect();
+
+    // Results are recorded in query order.
+    let mut statuses = Vec::with_capacity(job_results.len());
+    for (status, job_results) in job_results {
+        statuses.push(status);
+        results.extend(job_results);
+    }
+
+    let is_error = |status: &&i32| **status != errors::EX_OK;
+    let exit_status = if params.keep_going {
+        statuses.iter().rev().find(is_error)
+    } else {
+        statuses.iter().find(is_error)
+    };
+
+    *exit_status.unwrap_or(&errors::EX_OK)
 }
 
 /// Run a vector of custom commands using the configured shell.
 /// Parameters:
-/// - path: The current working directory for the command.
-/// - shell: The shell that will be used to run the command strings.
-/// - env: Environment variables to set.
-/// - cmd_seq_vec: Vector of vector of command strings to run.
-/// - arguments: Additional command line arguments available in $1, $2, $N.
+/// - options: Main options used to control verbosity.
+/// - params: CmdParams with the forwarded arguments, errexit, output and dry-run settings.
+/// - job: TreeJob with the tree's path, shell and environment.
+/// - evaluated_commands: Evaluated commands and their settings.
+/// - buffer: Receives the command output when using "--output=block".
 fn run_cmd_vec(
     options: &cli::MainOptions,
-    path: &str,
-    shell: &str,
-    env: &Vec<(String, String)>,
-    cmd_seq_vec: &[Vec<String>],
-    arguments: &[String],
-    exit_on_error: bool,
+    params: &CmdParams,
+    job: &TreeJob,
+    evaluated_commands: &[model::EvaluatedCommand],
+    buffer: &mut String,
 ) -> Result<(), i32> {
     // Get the current executable name
     let current_exe = cmd::current_exe();
     let mut exit_status = errors::EX_OK;
+    let exit_on_error = params.exit_on_error;
 
-    for cmd_seq in cmd_seq_vec {
-        for cmd_str in cmd_seq {
-            if options.verbose > 1 {
-                println!(
-                    "{} {}",
-                    model::Color::cyan(":"),
-                    model::Color::green(&cmd_str),
-                );
+    for evaluated in evaluated_commands {
+        let shell = evaluated.shell.as_deref().unwrap_or(job.shell.as_str());
+        // Relative "cwd" values are relative to the tree's path.
+        let cwd = match &evaluated.cwd {
+            Some(cwd) => std::path::Path::new(&job.path)
+                .join(cwd)
+                .to_string_lossy()
+                .to_string(),
+            None => job.path.clone(),
+        };
+        // Command environment variables are applied after the tree's environment.
+        let mut env = job.env.clone();
+        env.extend(evaluated.environment.iter().cloned());
+
+        if params.dry_run && (evaluated.cwd.is_some() || !evaluated.environment.is_empty()) {
+            for line in cmd::dry_run_preamble(&cwd, &evaluated.environment) {
+                let display = cmd::display_command(&line);
+                cmd::output_line(params.output, &job.name, buffer, &display);
             }
-            let mut exec = subprocess::Exec::cmd(shell).cwd(path);
+        }
+
+        // Command-line options take precedence over the command's settings.
+        let timeout = params
+            .timeout
+            .or(evaluated.timeout)
+            .map(std::time::Duration::from_secs);
+        let retries = params.retries.or(evaluated.retries).unwrap_or(0);
+        let retry_delay = std::time::Duration::from_secs(
+            params.retry_delay.or(evaluated.retry_delay).unwrap_or(0),
+        );
+
+        for cmd_str in &evaluated.commands {
+            // shell [-e] -c <command> <garden> [<arguments>...]
+            let mut command = vec![shell];
             if exit_on_error {
-                exec = exec.arg("-e");
+                command.push("-e");
+            }
+            command.push("-c");
+            command.push(cmd_str);
+            command.push(current_exe.as_str());
+            command.extend(params.arguments.iter().map(String::as_str));
+
+            if params.dry_run {
+                let display = cmd::display_command(&cmd::quote_command(&command));
+                cmd::output_line(params.output, &job.name, buffer, &display);
+                continue;
+            }
+            if options.verbose > 1 {
+                let display = cmd::display_command(cmd_str);
+                cmd::output_line(params.output, &job.name, buffer, &display);
             }
-            exec = exec
-                .arg("-c")
-                .arg(cmd_str)
-                .arg(current_exe.as_str())
-                .args(arguments);
-            // Update the command environment
-            for (k, v) in env {
-                exec = exec.env(k, v);
+            let mut attempt = 0;
+            let status = loop {
+                let status = cmd::join_with_output(
+                    &command,
+                    &cwd,
+                    &env,
+                    params.output,
+                    &job.name,
+                    buffer,
+                    timeout,
+                );
+                if status == errors::EX_OK || attempt >= retries {
+                    break status;
+                }
+                attempt += 1;
+                if !options.quiet {
+                    let message = retry_message(status, timeout, attempt, retries);
+                    cmd::output_message(params.output, &job.name, buffer, &message);
+                }
+                std::thread::sleep(retry_delay);
+            };
+            if status == errors::EX_TIMEOUT && timeout.is_some() && !options.quiet {
+                let message = timeout_message(timeout);
+                cmd::output_message(params.output, &job.name, buffer, &message);
             }
-            let status = cmd::status(exec.join());
             // When a command list is used then the return code from the final command
             // is the one that is returned when --no-errexit is in effect.
             if status != errors::EX_OK {
@@ -376,12 +995,137 @@ fn run_cmd_vec(
     Ok(())
 }
 
+/// Format the message displayed when a command is killed after exceeding its timeout.
+fn timeout_message(timeout: Option<std::time::Duration>) -> String {
+    let seconds = timeout.unwrap_or_default().as_secs();
+    format!(
+        "{}: command timed out after {} seconds",
+        model::Color::red("error").bold(),
+        seconds
+    )
+}
+
+/// Format the message displayed before retrying a failed command.
+fn retry_message(
+    status: i32,
+    timeout: Option<std::time::Duration>,
+    attempt: u64,
+    retries: u64,
+) -> String {
+    let reason = if status == errors::EX_TIMEOUT && timeout.is_some() {
+        timeout_message(timeout)
+    } else {
+        format!(
+            "{}: command returned exit status {}",
+            model::Color::red("error").bold(),
+            status
+        )
+    };
+    format!(
+        "{reason}\n{}",
+        model::Color::yellow(format!("retrying ({attempt}/{retries})"))
+    )
+}
+
+/// Prompt for confirmation before running a command with "confirm: true".
+/// Returns false when the answer is not "yes" or when stdin is closed.
+fn confirm_command(name: &str, tree: &str) -> bool {
+    let mut buffer = String::new();
+    eprint!(
+        "Run {} in {}? [{}/{}] ",
+        model::Color::green(format!("\"{name}\"")),
+        model::Color::blue(tree).bold(),
+        model::Color::blue("y"),
+        model::Color::blue("N"),
+    );
+    std::io::stderr().flush().unwrap_or(());
+
+    match std::io::stdin().read_line(&mut buffer) {
+        Ok(0) | Err(_) => {
+            eprintln!();
+            false
+        }
+        Ok(_) => matches!(buffer.trim().to_lowercase().as_str(), "y" | "yes"),
+    }
+}
+
+/// Restrict CmdParams to the commands that failed during the previous run.
+/// Queries and commands that were specified on the command-line limit the selection
+/// to the failed entries for those queries and commands.
+/// Returns false when there is nothing to re-run.
+fn select_failed(app: &model::ApplicationContext, params: &mut CmdParams) -> Result<bool> {
+    let path = failed_path(app.get_root_config());
+    let failed: Vec<report::CommandResult> = report::read_results(&path)?
+        .into_iter()
+        .filter(|result| params.queries.is_empty() || params.queries.contains(&result.query))
+        .filter(|result| params.commands.is_empty() || params.commands.contains(&result.command))
+        .collect();
+    if failed.is_empty() {
+        if !app.options.quiet {
+            eprintln!("# no failed commands to re-run");
+        }
+        return Ok(false);
+    }
+
+    let mut queries = IndexSet::new();
+    let mut commands = IndexSet::new();
+    for result in &failed {
+        queries.insert(result.query.clone());
+        commands.insert(result.command.clone());
+    }
+    params.queries = queries.into_iter().collect();
+    params.commands = commands.into_iter().collect();
+    params.failed = Some(failed);
+
+    Ok(true)
+}
+
+/// Return the path to the file that records the commands that failed during the last run.
+fn failed_path(config: &model::Configuration) -> std::path::PathBuf {
+    config.get_state_dir().join("failed.json")
+}
+
+/// Record the commands that failed so that they can be re-run using "--failed".
+fn save_failed(app: &model::ApplicationContext, results: &[report::CommandResult]) {
+    let path = failed_path(app.get_root_config());
+    let failed: Vec<report::CommandResult> = results
+        .iter()
+        .filter(|result| !result.is_ok())
+        .cloned()
+        .collect();
+    // The state directory is only created once there are failures to record.
+    if failed.is_empty() && !path.exists() {
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap_or(());
+    }
+    if let Err(err) = report::write_report(&path, model::ReportFormat::Json, &failed) {
+        if app.options.debug_level("cmd") > 0 {
+            debug!("{}", err);
+        }
+    }
+}
+
+/// Print the summary and write the report requested by "--summary" and "--report".
+fn report_results(params: &CmdParams, results: &[report::CommandResult]) -> Result<()> {
+    if params.summary {
+        report::print_summary(results);
+    }
+    if let Some(path) = &params.report {
+        report::write_report(path, params.report_format, results)?;
+    }
+
+    Ok(())
+}
+
 /// Run cmd() over a Vec of tree queries
 pub fn cmds(app: &mut model::ApplicationContext, params: &CmdParams) -> Result<()> {
     let mut exit_status = errors::EX_OK;
+    let mut results = Vec::new();
 
     for query in &params.queries {
-        let status = cmd(app, query, params).unwrap_or(errors::EX_IOERR);
+        let status = cmd(app, query, params, &mut results)?;
         if status != errors::EX_OK {
             exit_status = status;
             if !params.keep_going {
@@ -390,6 +1134,11 @@ pub fn cmds(app: &mut model::ApplicationContext, params: &CmdParams) -> Result<(
         }
     }
 
+    if !params.dry_run {
+        save_failed(app, &results);
+    }
+    report_results(params, &results)?;
+
     // Return the last non-zero exit status.
     cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
 }
@@ -6,6 +6,7 @@ use std::io::Write;
 
 use super::super::cli::MainOptions;
 use super::super::config;
+use super::cmd;
 
 /// Generate shell completions
 #[derive(Parser, Clone, Debug)]
@@ -27,34 +28,99 @@ pub fn main(options: &MainOptions, completion_options: &CompletionOptions) -> Re
     if completion_options.commands {
         let config = config::from_options(options)?;
         for name in config.commands.keys() {
-            cmd = cmd.subcommand(
-                Command::new(name)
-                    .about(format!("Custom {name} command"))
-                    .arg(
-                        Arg::new("keep_going")
-                            .help("Continue to the next tree when errors occur")
-                            .short('k')
-                            .long("keep-going"),
-                    )
-                    .arg(
-                        Arg::new("no_errexit")
-                            .help("Do not pass -e to the shell")
-                            .short('n')
-                            .long("no-errexit"),
-                    )
-                    .arg(
-                        Arg::new("queries")
-                            // NOTE: value_terminator may not be needed in future versions of clap_complete.
-                            // https://github.com/clap-rs/clap/pull/4612
-                            .value_terminator("--")
-                            .help("Tree queries to find trees where commands will be run"),
-                    )
-                    .arg(
-                        Arg::new("arguments")
-                            .help("Arguments to forward to custom commands")
-                            .last(true),
-                    ),
-            );
+            // Structured commands can provide a description for use in completions.
+            let about = match config.command_settings.get(name) {
+                Some(settings) if !settings.description.is_empty() => settings.description.clone(),
+                _ => format!("Custom {name} command"),
+            };
+            let parameters = config
+                .command_settings
+                .get(name)
+                .map(|settings| settings.parameters.as_slice())
+                .unwrap_or_default();
+            let custom = Command::new(name)
+                .about(about)
+                .arg(
+                    Arg::new("dry_run")
+                        .help("Print the commands that would be run without running them")
+                        .short('N')
+                        .long("dry-run"),
+                )
+                .arg(
+                    Arg::new("failed")
+                        .help("Re-run only the trees that failed during the previous run")
+                        .long("failed")
+                        .visible_alias("rerun-failed"),
+                )
+                .arg(
+                    Arg::new("num_jobs")
+                        .help("Number of trees to run commands in parallel")
+                        .short('j')
+                        .long("jobs"),
+                )
+                .arg(
+                    Arg::new("keep_going")
+                        .help("Continue to the next tree when errors occur")
+                        .short('k')
+                        .long("keep-going"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("Display command output directly, prefixed or in blocks")
+                        .long("output")
+                        .value_parser(["inherit", "prefix", "block"]),
+                )
+                .arg(
+                    Arg::new("no_errexit")
+                        .help("Do not pass -e to the shell")
+                        .short('n')
+                        .long("no-errexit"),
+                )
+                .arg(
+                    Arg::new("report")
+                        .help("Write the exit status and duration of each command to a report file")
+                        .long("report")
+                        .value_hint(clap::ValueHint::FilePath),
+                )
+                .arg(
+                    Arg::new("report_format")
+                        .help("File format used by --report")
+                        .long("report-format")
+                        .value_parser(["json", "junit"]),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .help("Retry failed commands up to the specified number of times")
+                        .long("retries"),
+                )
+                .arg(
+                    Arg::new("retry_delay")
+                        .help("Number of seconds to wait before retrying a failed command")
+                        .long("retry-delay"),
+                )
+                .arg(
+                    Arg::new("summary")
+                        .help("Print a summary of each command's exit status and duration")
+                        .long("summary"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .help("Kill commands that run longer than the specified number of seconds")
+                        .long("timeout"),
+                )
+                .arg(
+                    Arg::new("queries")
+                        // NOTE: value_terminator may not be needed in future versions of clap_complete.
+                        // https://github.com/clap-rs/clap/pull/4612
+                        .value_terminator("--")
+                        .help("Tree queries to find trees where commands will be run"),
+                )
+                .arg(
+                    Arg::new("arguments")
+                        .help("Arguments to forward to custom commands")
+                        .last(true),
+                );
+            cmd = cmd.subcommand(cmd::add_parameter_args(custom, parameters)?);
         }
     }
 
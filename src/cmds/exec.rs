@@ -10,6 +10,12 @@ use super::super::query;
 #[derive(Parser, Clone, Debug)]
 #[command(author, about, long_about)]
 pub struct ExecOptions {
+    /// Print the commands that would be run without running them
+    #[arg(long, short = 'N')]
+    dry_run: bool,
+    /// Display command output directly, prefixed by tree name, or in per-tree blocks
+    #[arg(long, value_enum, default_value_t = model::OutputMode::Inherit)]
+    output: model::OutputMode,
     /// Tree query for the gardens, groups or trees to run the command
     #[arg(value_hint=ValueHint::Other)]
     query: String,
@@ -34,6 +40,8 @@ pub fn main(app: &mut model::ApplicationContext, exec_options: &ExecOptions) ->
         config,
         quiet,
         verbose,
+        exec_options.output,
+        exec_options.dry_run,
         &exec_options.query,
         &exec_options.command,
     )
@@ -44,6 +52,8 @@ pub fn exec(
     config: &mut model::Configuration,
     quiet: bool,
     verbose: u8,
+    output: model::OutputMode,
+    dry_run: bool,
     query: &str,
     command: &[String],
 ) -> Result<()> {
@@ -74,7 +84,7 @@ pub fn exec(
         }
         // Run the command in the current context.
         if let Err(errors::GardenError::ExitStatus(status)) =
-            cmd::exec_in_context(config, context, quiet, verbose, command)
+            cmd::exec_in_context(config, context, quiet, verbose, output, dry_run, command)
         {
             exit_status = status;
         }
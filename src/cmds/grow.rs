@@ -1,17 +1,38 @@
 use super::super::cmd;
 use super::super::errors;
 use super::super::eval;
+use super::super::git;
 use super::super::model;
 use super::super::query;
 
+use super::cache;
+use super::lock;
+
 use anyhow::Result;
 use clap::Parser;
-use std::collections::HashSet;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
 
 /// Grow garden worktrees
 #[derive(Parser, Clone, Debug)]
 #[command(author, about, long_about)]
 pub struct GrowOptions {
+    /// Number of trees to grow in parallel. "0" uses all available cores
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    num_jobs: usize,
+    /// Fetch from all remotes in existing trees
+    #[arg(long, short)]
+    fetch: bool,
+    /// Fetch from all remotes and fast-forward the current branch in existing trees
+    #[arg(long, short)]
+    update: bool,
+    /// Check out the commits and remote URLs recorded in "garden.lock"
+    #[arg(long)]
+    locked: bool,
+    /// Remove remotes that are not configured in existing trees
+    #[arg(long)]
+    prune_remotes: bool,
     /// Tree query for the gardens, groups or trees to grow
     #[arg(required = true)]
     queries: Vec<String>,
@@ -19,176 +40,763 @@ pub struct GrowOptions {
 
 /// Main entry point for the "garden grow" command
 pub fn main(app: &mut model::ApplicationContext, options: &GrowOptions) -> Result<()> {
-    let quiet = app.options.quiet;
-    let verbose = app.options.verbose;
+    let params = GrowParams {
+        num_jobs: options.num_jobs,
+        quiet: app.options.quiet,
+        verbose: app.options.verbose,
+        fetch: options.fetch || options.update,
+        update: options.update,
+        locked: options.locked,
+        prune_remotes: options.prune_remotes,
+    };
+    let config = app.get_root_config();
+    let exit_status = grow(config, &options.queries, &params)?;
 
-    let mut exit_status = errors::EX_OK;
-    let mut configured_worktrees: HashSet<String> = HashSet::new();
-    let config = app.get_root_config_mut();
-    for query in &options.queries {
-        let status = grow(config, &mut configured_worktrees, quiet, verbose, query)?;
-        if status != errors::EX_OK {
-            exit_status = status;
+    // Return the last non-zero exit status.
+    cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
+}
+
+/// GrowParams are used to control how trees are grown.
+#[derive(Clone, Debug, Default)]
+pub struct GrowParams {
+    /// Number of trees to grow in parallel.
+    pub num_jobs: usize,
+    /// Suppress the tree headers.
+    pub quiet: bool,
+    /// Display the commands that are run.
+    pub verbose: u8,
+    /// Fetch from all remotes.
+    pub fetch: bool,
+    /// Fast-forward the current branch to its upstream branch.
+    pub update: bool,
+    /// Check out the commits recorded in "garden.lock".
+    pub locked: bool,
+    /// Remove remotes that are not configured.
+    pub prune_remotes: bool,
+}
+
+/// GrowState contains the state that is shared between concurrent jobs.
+#[derive(Debug, Default)]
+struct GrowState {
+    /// Repositories created using "git worktree" share a common Git configuration
+    /// and only need to be configured and fetched once.
+    configured_worktrees: Mutex<HashSet<String>>,
+    /// Trees that could not be fast-forwarded.
+    not_updated: Mutex<Vec<String>>,
+    /// Mirrors in "garden.cache" that are shared by multiple trees are created once.
+    mirror_locks: HashMap<String, Mutex<()>>,
+}
+
+/// GrowJob contains the evaluated settings needed to grow a single tree.
+/// Trees are evaluated on the main thread so that jobs can run in parallel.
+#[derive(Clone, Debug)]
+struct GrowJob {
+    /// Name of the tree.
+    tree: String,
+    /// Path to the tree.
+    path: String,
+    /// Header displayed when the tree is grown.
+    header: String,
+    /// Path to the repository that is shared by all of its worktrees.
+    shared_worktree_path: String,
+    /// Bare repositories do not have a branch that can be fast-forwarded.
+    is_bare_repository: bool,
+    /// How submodules are initialized.
+    submodules: model::Submodules,
+    /// Partial clone filter, e.g. "blob:none".
+    clone_filter: String,
+    /// Directories included in a cone mode sparse checkout.
+    sparse_checkout: Vec<String>,
+    /// The tag, commit or ref that the tree is pinned to.
+    pin: Option<model::TreePin>,
+    /// Branch recorded in "garden.lock". The branch is reset to the locked commit.
+    locked_branch: String,
+    /// How the tree is created when it does not exist.
+    kind: GrowKind,
+    /// Remote names and URLs.
+    remotes: Vec<(String, String)>,
+    /// Remove remotes that are not configured.
+    prune_remotes: bool,
+    /// Patterns for unconfigured remotes that are not removed.
+    remotes_keep: Vec<glob::Pattern>,
+    /// Git configuration names and values. Empty values unset the setting.
+    gitconfig: Vec<(String, Vec<String>)>,
+}
+
+/// GrowKind specifies how a missing tree is created.
+#[derive(Clone, Debug)]
+enum GrowKind {
+    /// Trees without an "origin" remote are not created.
+    Missing,
+    /// Symlink trees point to another path.
+    Symlink { target: String },
+    /// Repositories are created using "git clone".
+    /// The optional mirror is a bare repository in "garden.cache".
+    Clone {
+        command: Vec<String>,
+        url: String,
+        mirror: Option<String>,
+    },
+    /// Worktrees are created using "git worktree add" after their parent has been grown.
+    /// The "git worktree add" command depends on the branches that exist in the parent.
+    Worktree {
+        parent: usize,
+        parent_path: String,
+        worktree: String,
+        /// Path to the worktree relative to its parent.
+        relative_path: String,
+        branch: String,
+        /// Remote-tracking branch to track, e.g. "origin/{branch}".
+        upstream: String,
+        /// Ref used to create the branch when the upstream branch does not exist.
+        base: String,
+    },
+}
+
+/// Create/update trees in the evaluated tree queries.
+/// Up to "num_jobs" trees are grown in parallel. Worktrees are grown after their parent.
+pub fn grow(config: &model::Configuration, queries: &[String], params: &GrowParams) -> Result<i32> {
+    let mut contexts = Vec::new();
+    for query in queries {
+        contexts.append(&mut query::resolve_trees(config, query));
+    }
+
+    grow_contexts(config, &contexts, params)
+}
+
+/// Create/update the trees for the specified tree contexts.
+pub fn grow_contexts(
+    config: &model::Configuration,
+    contexts: &[model::TreeContext],
+    params: &GrowParams,
+) -> Result<i32> {
+    let mut jobs = grow_jobs(config, contexts, params.verbose)?;
+    if params.locked {
+        let locked = lock::read(&lock::lock_path(config)?)?;
+        apply_locked_trees(&mut jobs, &locked);
+    }
+    let state = GrowState {
+        mirror_locks: jobs
+            .iter()
+            .filter_map(|job| match &job.kind {
+                GrowKind::Clone {
+                    mirror: Some(mirror),
+                    ..
+                } => Some((mirror.clone(), Mutex::new(()))),
+                _ => None,
+            })
+            .collect(),
+        ..GrowState::default()
+    };
+    let mut statuses = vec![errors::EX_OK; jobs.len()];
+
+    if params.num_jobs == 1 {
+        for (index, job) in jobs.iter().enumerate() {
+            let status = grow_tree(job, &statuses, &state, params)?;
+            statuses[index] = status;
+        }
+    } else {
+        cmd::initialize_threads(params.num_jobs);
+        // Commands that modify the same repository are not run concurrently.
+        let repository_locks: HashMap<&str, Mutex<()>> = jobs
+            .iter()
+            .map(|job| (job.shared_worktree_path.as_str(), Mutex::new(())))
+            .collect();
+        for level in grow_levels(&jobs) {
+            let level_statuses: Vec<Result<i32>> = level
+                .par_iter()
+                .map(|index| {
+                    let job = &jobs[*index];
+                    let _lock = repository_locks
+                        .get(job.shared_worktree_path.as_str())
+                        .map(|lock| lock.lock().unwrap_or_else(|err| err.into_inner()));
+                    grow_tree(job, &statuses, &state, params)
+                })
+                .collect();
+            for (index, status) in level.iter().zip(level_statuses) {
+                statuses[*index] = status?;
+            }
+        }
+    }
+
+    // Report the trees that could not be fast-forwarded.
+    let mut not_updated = state
+        .not_updated
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner());
+    if !not_updated.is_empty() {
+        not_updated.sort();
+        eprintln!(
+            "{} unable to fast-forward the following trees:",
+            model::Color::red("error:").bold(),
+        );
+        for tree in &not_updated {
+            eprintln!("  {}", model::Color::blue(tree).bold());
         }
     }
 
     // Return the last non-zero exit status.
-    cmd::result_from_exit_status(exit_status).map_err(|err| err.into())
+    let exit_status = statuses
+        .into_iter()
+        .rev()
+        .find(|status| *status != errors::EX_OK);
+
+    Ok(exit_status.unwrap_or(errors::EX_OK))
 }
 
-/// Create/update trees in the evaluated tree query.
-pub fn grow(
-    config: &mut model::Configuration,
-    configured_worktrees: &mut HashSet<String>,
-    quiet: bool,
+/// Evaluate the tree contexts into GrowJobs.
+/// Trees that resolve to the same path are only grown once. The gitconfig settings
+/// from every context are applied, with later contexts taking precedence.
+fn grow_jobs(
+    config: &model::Configuration,
+    contexts: &[model::TreeContext],
     verbose: u8,
-    query: &str,
-) -> Result<i32> {
-    let contexts = query::resolve_trees(config, query);
-    let mut exit_status = errors::EX_OK;
+) -> Result<Vec<GrowJob>> {
+    let mut jobs = Vec::new();
+    let mut indexes = HashMap::new();
+    for ctx in contexts {
+        add_grow_job(config, ctx, verbose, &mut jobs, &mut indexes)?;
+    }
 
-    for ctx in &contexts {
-        let status = grow_tree_from_context(config, configured_worktrees, ctx, quiet, verbose)?;
-        if status != errors::EX_OK {
-            // Return the last non-zero exit status.
-            exit_status = status;
+    Ok(jobs)
+}
+
+/// Pin jobs to the commits and "origin" URLs recorded in "garden.lock".
+/// Trees that are not present in the lock file are grown as configured.
+fn apply_locked_trees(jobs: &mut [GrowJob], locked: &lock::LockedTrees) {
+    for job in jobs.iter_mut() {
+        let locked_tree = match locked.get(&job.tree) {
+            Some(locked_tree) => locked_tree,
+            None => {
+                if !matches!(job.kind, GrowKind::Symlink { .. }) {
+                    eprintln!(
+                        "{} {} is not present in garden.lock",
+                        model::Color::yellow("warning:").bold(),
+                        job.tree
+                    );
+                }
+                continue;
+            }
+        };
+        job.pin = Some(model::TreePin::Commit(locked_tree.commit.clone()));
+        job.locked_branch = locked_tree.branch.clone();
+        if locked_tree.url.is_empty() {
+            continue;
+        }
+        for (remote, url) in job.remotes.iter_mut() {
+            if remote == "origin" {
+                *url = locked_tree.url.clone();
+            }
+        }
+        if let GrowKind::Clone { command, url, .. } = &mut job.kind {
+            // The command ends with "<url> <path>".
+            let url_index = command.len() - 2;
+            command[url_index] = locked_tree.url.clone();
+            *url = locked_tree.url.clone();
         }
     }
-
-    Ok(exit_status)
 }
 
-/// Grow the tree specified by the context into existence.
-/// Trees without remotes are silently ignored.
-fn grow_tree_from_context(
+/// Evaluate a tree and add it to the jobs. Returns the index of the tree's job.
+/// Worktree parents are added before their children.
+fn add_grow_job(
     config: &model::Configuration,
-    configured_worktrees: &mut HashSet<String>,
     ctx: &model::TreeContext,
-    quiet: bool,
     verbose: u8,
-) -> Result<i32> {
-    let mut exit_status = errors::EX_OK;
+    jobs: &mut Vec<GrowJob>,
+    indexes: &mut HashMap<String, usize>,
+) -> Result<Option<usize>> {
     let tree = match config.trees.get(&ctx.tree) {
         Some(tree) => tree,
-        None => return Ok(exit_status),
+        None => return Ok(None),
     };
-
     let path = tree.path_as_ref()?.clone();
-    model::print_tree_details(tree, verbose, quiet);
-
-    let pathbuf = std::path::PathBuf::from(&path);
-    let parent = pathbuf.parent().ok_or_else(|| {
-        errors::GardenError::AssertionError(format!("unable to get parent directory for {path}"))
-    })?;
-    std::fs::create_dir_all(parent)
-        .map_err(|err| errors::GardenError::OSError(format!("unable to create {path}: {err}")))?;
-
-    if pathbuf.exists() {
-        return update_tree_from_context(
-            config,
-            configured_worktrees,
-            ctx,
-            &pathbuf,
-            quiet,
-            verbose,
-        );
+    if let Some(index) = indexes.get(&path) {
+        // Trees that are reached through multiple garden contexts are grown once
+        // using the gitconfig settings from every context.
+        let job = &mut jobs[*index];
+        if job.tree == ctx.tree && !tree.is_symlink {
+            merge_gitconfig(&mut job.gitconfig, eval::tree_gitconfig(config, ctx));
+        }
+        return Ok(Some(*index));
     }
 
-    if tree.is_symlink {
-        let status = grow_symlink(config, ctx).unwrap_or(errors::EX_IOERR);
-        if status != errors::EX_OK {
-            exit_status = status;
+    let kind = if tree.is_symlink {
+        GrowKind::Symlink {
+            target: tree.symlink_as_ref().cloned().unwrap_or_default(),
         }
-        return Ok(exit_status);
+    } else if tree.is_worktree {
+        worktree_kind(config, ctx, verbose, jobs, indexes)?
+    } else {
+        clone_kind(config, ctx, &path)
+    };
+
+    // Existing symlinks require no further processing.
+    let mut remotes = Vec::new();
+    let mut gitconfig = Vec::new();
+    if !tree.is_symlink {
+        for (remote, var) in &tree.remotes {
+            let url = eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref());
+            remotes.push((remote.clone(), url));
+        }
+        gitconfig = eval::tree_gitconfig(config, ctx);
     }
 
-    if tree.is_worktree {
-        return grow_tree_from_context_as_worktree(
+    let index = jobs.len();
+    jobs.push(GrowJob {
+        tree: ctx.tree.clone(),
+        header: model::display_tree(tree, &path, verbose),
+        shared_worktree_path: query::shared_worktree_path(config, ctx),
+        is_bare_repository: tree.is_bare_repository,
+        submodules: tree.submodules,
+        clone_filter: eval::tree_value(
             config,
-            configured_worktrees,
-            ctx,
-            quiet,
-            verbose,
-        );
+            tree.clone_filter.get_expr(),
+            &ctx.tree,
+            ctx.garden.as_ref(),
+        ),
+        sparse_checkout: tree
+            .sparse_checkout
+            .iter()
+            .map(|var| eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref()))
+            .filter(|path| !path.is_empty())
+            .collect(),
+        pin: eval::tree_pin(config, &ctx.tree, ctx.garden.as_ref()),
+        locked_branch: String::new(),
+        path: path.clone(),
+        kind,
+        remotes,
+        prune_remotes: tree.is_remotes_exclusive,
+        remotes_keep: tree
+            .remotes_keep
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .collect(),
+        gitconfig,
+    });
+    indexes.insert(path, index);
+
+    Ok(Some(index))
+}
+
+/// Merge gitconfig settings into an existing list of settings.
+/// Settings from later contexts take precedence over earlier ones.
+fn merge_gitconfig(gitconfig: &mut Vec<(String, Vec<String>)>, other: Vec<(String, Vec<String>)>) {
+    for (name, values) in other {
+        match gitconfig.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing_values)) => *existing_values = values,
+            None => gitconfig.push((name, values)),
+        }
     }
+}
 
+/// Evaluate the "git clone" command for a tree.
+/// Trees without an "origin" remote are not cloned.
+fn clone_kind(config: &model::Configuration, ctx: &model::TreeContext, path: &str) -> GrowKind {
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return GrowKind::Missing,
+    };
     // The "origin" remote is cloned by convention. The "url" field maps to "origin".
     let url = match tree.remotes.get("origin") {
         Some(remote) => eval::tree_value(config, remote.get_expr(), &ctx.tree, ctx.garden.as_ref()),
-        None => return Ok(exit_status),
+        None => return GrowKind::Missing,
     };
 
     // git clone [options] <url> <path>
-    let mut cmd: Vec<&str> = ["git", "clone"].to_vec();
+    let mut command = vec![string!("git"), string!("clone")];
 
     // [options]
     //
     // "git clone --bare" clones bare repositories.
     if tree.is_bare_repository {
-        cmd.push("--bare");
+        command.push(string!("--bare"));
     }
-
     // "git clone --branch=name" clones the named branch.
-    let branch_var = tree.branch.clone();
     let branch = eval::tree_value(
         config,
-        branch_var.get_expr(),
+        tree.branch.get_expr(),
         &ctx.tree,
         ctx.garden.as_ref(),
     );
-    let branch_opt;
     if !branch.is_empty() {
-        branch_opt = format!("--branch={branch}");
-        cmd.push(&branch_opt);
+        command.push(format!("--branch={branch}"));
     }
     // "git clone --depth=N" creates shallow clones with truncated history.
-    let clone_depth = tree.clone_depth;
-    let clone_depth_opt;
-    if clone_depth > 0 {
-        clone_depth_opt = format!("--depth={clone_depth}");
-        cmd.push(&clone_depth_opt);
+    if tree.clone_depth > 0 {
+        command.push(format!("--depth={}", tree.clone_depth));
+    }
+    // "git clone --filter=blob:none" creates partial clones.
+    let clone_filter = eval::tree_value(
+        config,
+        tree.clone_filter.get_expr(),
+        &ctx.tree,
+        ctx.garden.as_ref(),
+    );
+    if !clone_filter.is_empty() {
+        command.push(format!("--filter={clone_filter}"));
+    }
+    // "git clone --sparse" checks out the top-level files only. The sparse checkout
+    // paths are configured after the repository has been cloned.
+    if !tree.sparse_checkout.is_empty() && !tree.is_bare_repository {
+        command.push(string!("--sparse"));
     }
     // "git clone --depth=N" clones a single branch by default.
     // We generally want all branches available in our clones so we default to
     // "single-branch: false" so that "--no-single-branch" is used. This makes
     // all branches available by default.
-    let is_single_branch = tree.is_single_branch;
-    if is_single_branch {
-        cmd.push("--single-branch");
+    if tree.is_single_branch {
+        command.push(string!("--single-branch"));
     } else {
-        cmd.push("--no-single-branch");
+        command.push(string!("--no-single-branch"));
+    }
+    // "git clone --recurse-submodules" initializes nested submodules.
+    if tree.submodules == model::Submodules::Recursive && !tree.is_bare_repository {
+        command.push(string!("--recurse-submodules"));
+    }
+
+    // "git clone --reference-if-able" borrows objects from a bare mirror in "garden.cache".
+    let mirror = config
+        .get_cache_path()
+        .map(|cache_path| cache::mirror_path(&cache_path, &url))
+        .map(|mirror_path| mirror_path.to_string_lossy().to_string());
+    if let Some(mirror) = &mirror {
+        command.push(string!("--reference-if-able"));
+        command.push(mirror.clone());
+        if config.cache_dissociate {
+            command.push(string!("--dissociate"));
+        }
     }
 
     // <url> <path>
-    cmd.push(&url);
-    cmd.push(&path);
-    if verbose > 1 {
-        print_quoted_command(&cmd);
+    command.push(url.clone());
+    command.push(path.to_string());
+
+    GrowKind::Clone {
+        command,
+        url,
+        mirror,
     }
+}
 
-    let exec = cmd::exec_cmd(&cmd);
-    let status = cmd::status(exec.join());
-    if status != 0 {
-        exit_status = status;
+/// Evaluate the "git worktree add" command for a worktree.
+/// The parent worktree is added to the jobs before the worktree.
+fn worktree_kind(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+    verbose: u8,
+    jobs: &mut Vec<GrowJob>,
+    indexes: &mut HashMap<String, usize>,
+) -> Result<GrowKind> {
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return Ok(GrowKind::Missing),
+    };
+    let worktree = eval::tree_value(
+        config,
+        tree.worktree.get_expr(),
+        &ctx.tree,
+        ctx.garden.as_ref(),
+    );
+    let branch = eval::tree_value(
+        config,
+        tree.branch.get_expr(),
+        &ctx.tree,
+        ctx.garden.as_ref(),
+    );
+    let worktree_not_found = || errors::GardenError::WorktreeNotFound {
+        tree: tree.get_name().to_string(),
+        worktree: worktree.clone(),
+    };
+    let parent_ctx =
+        query::tree_from_name(config, &worktree, ctx.garden.as_ref(), ctx.group.as_ref())
+            .ok_or_else(worktree_not_found)?;
+    let parent = add_grow_job(config, &parent_ctx, verbose, jobs, indexes)?
+        .ok_or_else(worktree_not_found)?;
+    let parent_path = jobs[parent].path.clone();
+    let tree_path = tree.path_as_ref()?;
+
+    // The parent_path is the base path from which we'll execute "git worktree add".
+    // Compute a relative path to the child.
+    let relative_path = match pathdiff::diff_paths(tree_path, &parent_path) {
+        Some(relative_path) => relative_path.to_string_lossy().to_string(),
+        None => tree_path.clone(),
+    };
+
+    // "branches.<branch>" settings take precedence over the tree's "upstream" and "base".
+    let evaluate = |var: &model::Variable| {
+        eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref())
+    };
+    let branch_settings = tree.branches.get(&branch);
+    let mut upstream = branch_settings
+        .map(|settings| evaluate(&settings.upstream))
+        .unwrap_or_default();
+    if upstream.is_empty() {
+        upstream = evaluate(&tree.upstream);
+    }
+    if upstream.is_empty() && !branch.is_empty() {
+        upstream = format!("origin/{branch}");
+    }
+    let mut base = branch_settings
+        .map(|settings| evaluate(&settings.base))
+        .unwrap_or_default();
+    if base.is_empty() {
+        base = evaluate(&tree.base);
     }
 
-    let status =
-        update_tree_from_context(config, configured_worktrees, ctx, &pathbuf, quiet, verbose)?;
-    if status != errors::EX_OK {
-        exit_status = status;
+    Ok(GrowKind::Worktree {
+        parent,
+        parent_path,
+        worktree,
+        relative_path,
+        branch,
+        upstream,
+        base,
+    })
+}
+
+/// Group jobs into levels that can run in parallel.
+/// Worktrees are placed in the level after their parent.
+fn grow_levels(jobs: &[GrowJob]) -> Vec<Vec<usize>> {
+    let mut depths: Vec<usize> = Vec::with_capacity(jobs.len());
+    let mut levels: Vec<Vec<usize>> = Vec::new();
+    for (index, job) in jobs.iter().enumerate() {
+        // Parents are always added to the jobs before their children.
+        let depth = match job.kind {
+            GrowKind::Worktree { parent, .. } => depths[parent] + 1,
+            _ => 0,
+        };
+        depths.push(depth);
+        if levels.len() <= depth {
+            levels.resize(depth + 1, Vec::new());
+        }
+        levels[depth].push(index);
     }
-    Ok(exit_status)
+
+    levels
 }
 
-/// Print a command that will be executed.
-fn print_quoted_command(command: &[&str]) {
-    let mut quoted_args: Vec<String> = Vec::new();
-    for cmd in command {
-        let quoted = shlex::quote(cmd);
-        quoted_args.push(quoted.as_ref().to_string());
+/// Grow the tree specified by the job into existence.
+/// "statuses" contains the exit status for jobs that have already run.
+fn grow_tree(
+    job: &GrowJob,
+    statuses: &[i32],
+    state: &GrowState,
+    params: &GrowParams,
+) -> Result<i32> {
+    let verbose = params.verbose;
+    if !params.quiet {
+        eprintln!("{}", job.header);
+    }
+    let path = &job.path;
+    let pathbuf = std::path::PathBuf::from(path);
+    let parent = pathbuf.parent().ok_or_else(|| {
+        errors::GardenError::AssertionError(format!("unable to get parent directory for {path}"))
+    })?;
+    std::fs::create_dir_all(parent)
+        .map_err(|err| errors::GardenError::OSError(format!("unable to create {path}: {err}")))?;
+
+    if pathbuf.exists() {
+        let mut exit_status = update_tree(job, state, params)?;
+        // Pinned trees are checked out at their pinned revision instead of being fast-forwarded.
+        if (params.update || params.locked) && exit_status == errors::EX_OK {
+            exit_status = if job.pin.is_some() {
+                checkout_pin(job, verbose)
+            } else if params.update {
+                fast_forward_tree(job, state, verbose)
+            } else {
+                errors::EX_OK
+            };
+        }
+        if exit_status == errors::EX_OK {
+            exit_status = update_checkout(job, verbose);
+        }
+        return Ok(exit_status);
     }
 
-    print_command_str(&quoted_args.join(" "));
+    match &job.kind {
+        GrowKind::Missing => Ok(errors::EX_OK),
+        GrowKind::Symlink { target } => Ok(grow_symlink(job, target).unwrap_or(errors::EX_IOERR)),
+        GrowKind::Worktree {
+            parent,
+            parent_path,
+            worktree,
+            relative_path,
+            branch,
+            upstream,
+            base,
+        } => {
+            if statuses[*parent] != errors::EX_OK {
+                return Err(errors::GardenError::WorktreeParentCreationError {
+                    tree: job.tree.clone(),
+                    worktree: worktree.clone(),
+                }
+                .into());
+            }
+            let (command, new_branch_upstream) =
+                worktree_add_command(parent_path, relative_path, branch, upstream, base, verbose);
+            if verbose > 1 {
+                print_quoted_command(&command);
+            }
+            let exec = cmd::exec_in_dir(&command, parent_path);
+            let exit_status = cmd::status(exec.join());
+            if exit_status != errors::EX_OK {
+                return Err(errors::GardenError::WorktreeGitCheckoutError {
+                    tree: job.tree.clone(),
+                    status: exit_status,
+                }
+                .into());
+            }
+            if let Some((remote, merge)) = new_branch_upstream {
+                let exit_status =
+                    set_branch_upstream(parent_path, branch, &remote, &merge, verbose);
+                if exit_status != errors::EX_OK {
+                    return Ok(exit_status);
+                }
+            }
+            let exit_status = checkout_pin(job, verbose);
+            if exit_status != errors::EX_OK {
+                return Ok(exit_status);
+            }
+            Ok(update_checkout(job, verbose))
+        }
+        GrowKind::Clone {
+            command,
+            url,
+            mirror,
+        } => {
+            if let Some(mirror) = mirror {
+                // Missing mirrors are created. Clones proceed without a mirror on failure.
+                let _lock = state
+                    .mirror_locks
+                    .get(mirror)
+                    .map(|lock| lock.lock().unwrap_or_else(|err| err.into_inner()));
+                cache::create_mirror(url, mirror, verbose);
+            }
+            if verbose > 1 {
+                print_quoted_command(command);
+            }
+            let mut exit_status = errors::EX_OK;
+            let exec = cmd::exec_cmd(command);
+            let mut status = cmd::status(exec.join());
+            // Clone from the mirror when the remote is unavailable, e.g. when offline.
+            // The "origin" remote is pointed back to the url by update_tree().
+            if let Some(mirror) = mirror {
+                if status != errors::EX_OK && std::path::Path::new(mirror).exists() {
+                    let mut mirror_command = command.clone();
+                    // The command ends with "<url> <path>".
+                    let url_index = mirror_command.len() - 2;
+                    mirror_command[url_index] = mirror.clone();
+                    if verbose > 1 {
+                        print_quoted_command(&mirror_command);
+                    }
+                    let exec = cmd::exec_cmd(&mirror_command);
+                    status = cmd::status(exec.join());
+                }
+            }
+            if status != errors::EX_OK {
+                exit_status = status;
+            }
+            let status = update_tree(job, state, params)?;
+            if status != errors::EX_OK {
+                exit_status = status;
+            }
+            if exit_status == errors::EX_OK {
+                exit_status = checkout_pin(job, verbose);
+            }
+            if exit_status == errors::EX_OK {
+                exit_status = update_checkout(job, verbose);
+            }
+            Ok(exit_status)
+        }
+    }
+}
+
+/// Build the "git worktree add" command for a worktree.
+/// Existing local branches are checked out as-is. New branches track the upstream
+/// branch when it exists. Otherwise the branch is created from the base ref, or from
+/// the parent's HEAD, and the remote and branch that it should track once it has been
+/// pushed are returned alongside the command.
+fn worktree_add_command(
+    parent_path: &str,
+    relative_path: &str,
+    branch: &str,
+    upstream: &str,
+    base: &str,
+    verbose: u8,
+) -> (Vec<String>, Option<(String, String)>) {
+    let mut command = vec![string!("git"), string!("worktree"), string!("add")];
+    if branch.is_empty() {
+        command.push(relative_path.to_string());
+        return (command, None);
+    }
+    if is_local_branch(parent_path, branch) {
+        command.push(relative_path.to_string());
+        command.push(branch.to_string());
+        return (command, None);
+    }
+
+    // Fetch the upstream remote when the remote-tracking branch is not yet available.
+    let remote_branch = upstream.split_once('/');
+    let upstream_ref = format!("refs/remotes/{upstream}");
+    let mut has_upstream = git::rev_parse_commit(parent_path, &upstream_ref).is_some();
+    if !has_upstream {
+        if let Some((remote, _)) = remote_branch {
+            let fetch = ["git", "fetch", "--quiet", remote];
+            if verbose > 1 {
+                print_quoted_command(&fetch);
+            }
+            let exec = cmd::exec_in_dir(&fetch, parent_path).stderr(subprocess::NullFile);
+            if cmd::status(exec.join()) == errors::EX_OK {
+                has_upstream = git::rev_parse_commit(parent_path, &upstream_ref).is_some();
+            }
+        }
+    }
+
+    if has_upstream {
+        command.push(string!("--track"));
+        command.push(string!("-b"));
+        command.push(branch.to_string());
+        command.push(relative_path.to_string());
+        command.push(upstream.to_string());
+        return (command, None);
+    }
+
+    command.push(string!("--no-track"));
+    command.push(string!("-b"));
+    command.push(branch.to_string());
+    command.push(relative_path.to_string());
+    if !base.is_empty() {
+        command.push(base.to_string());
+    }
+    let new_branch_upstream = remote_branch
+        .map(|(remote, remote_branch)| (remote.to_string(), format!("refs/heads/{remote_branch}")));
+
+    (command, new_branch_upstream)
+}
+
+/// Configure the remote and branch that a new local branch tracks once it has been pushed.
+fn set_branch_upstream(path: &str, branch: &str, remote: &str, merge: &str, verbose: u8) -> i32 {
+    let remote_key = format!("branch.{branch}.remote");
+    let merge_key = format!("branch.{branch}.merge");
+    for (key, value) in [(remote_key, remote), (merge_key, merge)] {
+        let command = ["git", "config", key.as_str(), value];
+        if verbose > 1 {
+            print_quoted_command(&command);
+        }
+        let exec = cmd::exec_in_dir(&command, path);
+        let exit_status = cmd::status(exec.join());
+        if exit_status != errors::EX_OK {
+            return exit_status;
+        }
+    }
+
+    errors::EX_OK
+}
+
+/// Print a command that will be executed.
+fn print_quoted_command<S: AsRef<str>>(command: &[S]) {
+    print_command_str(&cmd::quote_command(command));
 }
 
 /// Print a command that will be executed from a string.
@@ -197,32 +805,27 @@ fn print_command_str(cmd: &str) {
 }
 
 /// Add remotes that do not already exist and synchronize .git/config values.
-fn update_tree_from_context(
-    config: &model::Configuration,
-    configured_worktrees: &mut HashSet<String>,
-    ctx: &model::TreeContext,
-    path: &std::path::Path,
-    _quiet: bool,
-    verbose: u8,
-) -> Result<i32> {
+/// Remotes are fetched when "--fetch" is specified.
+fn update_tree(job: &GrowJob, state: &GrowState, params: &GrowParams) -> Result<i32> {
+    let verbose = params.verbose;
     let mut exit_status = errors::EX_OK;
-    let tree = match config.trees.get(&ctx.tree) {
-        Some(tree) => tree,
-        None => return Ok(exit_status),
-    };
-
     // Existing symlinks require no further processing.
-    if tree.is_symlink {
+    if let GrowKind::Symlink { .. } = job.kind {
         return Ok(exit_status);
     }
 
     // Repositories created using "git worktree" share a common Git configuration
     // and only need to be configured once. Skip configuring the repository
     // if we've already processed it.
-    let shared_worktree_path = query::shared_worktree_path(config, ctx);
-    if !configured_worktrees.insert(shared_worktree_path) {
+    let is_configured = !state
+        .configured_worktrees
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(job.shared_worktree_path.clone());
+    if is_configured {
         return Ok(exit_status);
     }
+    let path = std::path::Path::new(&job.path);
 
     // Gather existing remotes
     let mut existing_remotes = HashSet::new();
@@ -238,9 +841,7 @@ fn update_tree_from_context(
     }
 
     // Loop over remotes and add/update the git remote configuration.
-    for (remote, var) in &tree.remotes {
-        let url = eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref());
-
+    for (remote, url) in &job.remotes {
         let exec = if existing_remotes.contains(remote) {
             let remote_key = format!("remote.{remote}.url");
             let command = ["git", "config", remote_key.as_ref(), url.as_ref()];
@@ -262,14 +863,75 @@ fn update_tree_from_context(
         }
     }
 
+    // Remove remotes that are not configured. Trees without any configured remotes
+    // are left as-is so that repositories with unmanaged remotes are not affected.
+    if (params.prune_remotes || job.prune_remotes) && !job.remotes.is_empty() {
+        let mut stale_remotes: Vec<&String> = existing_remotes
+            .iter()
+            .filter(|remote| !job.remotes.iter().any(|(name, _)| name == *remote))
+            .filter(|remote| !job.remotes_keep.iter().any(|keep| keep.matches(remote)))
+            .collect();
+        stale_remotes.sort();
+        for remote in stale_remotes {
+            let command = ["git", "remote", "remove", remote.as_str()];
+            if verbose > 1 {
+                print_quoted_command(&command);
+            }
+            let exec = cmd::exec_in_dir(&command, path);
+            let status = cmd::status(exec.join());
+            if status != errors::EX_OK {
+                exit_status = status;
+            }
+        }
+    }
+
     // Set gitconfig settings
-    for (var_name, var) in &tree.gitconfig {
-        let name = eval::tree_value(config, var_name, &ctx.tree, ctx.garden.as_ref());
-        let value = match var.get_value() {
-            Some(precomputed_value) => precomputed_value.to_string(),
-            None => eval::tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref()),
+    for (name, values) in &job.gitconfig {
+        let status = update_gitconfig(path, name, values, verbose);
+        if status != errors::EX_OK {
+            exit_status = status;
+        }
+    }
+
+    // Record the partial clone filter so that subsequent fetches are filtered.
+    let has_origin = job.remotes.iter().any(|(remote, _)| remote == "origin");
+    if !job.clone_filter.is_empty() && has_origin {
+        let command = ["git", "config", "remote.origin.partialclonefilter"];
+        let exec = cmd::exec_in_dir(&command, path);
+        let current_filter = match cmd::capture_stdout(exec) {
+            Ok(capture) => cmd::trim_stdout(&capture),
+            Err(_) => String::new(),
         };
-        let command = ["git", "config", name.as_ref(), value.as_ref()];
+        if current_filter != job.clone_filter {
+            for (name, value) in [
+                ("remote.origin.promisor", "true"),
+                (
+                    "remote.origin.partialclonefilter",
+                    job.clone_filter.as_str(),
+                ),
+            ] {
+                let command = ["git", "config", name, value];
+                if verbose > 1 {
+                    print_quoted_command(&command);
+                }
+                let exec = cmd::exec_in_dir(&command, path);
+                let status = cmd::status(exec.join());
+                if status != errors::EX_OK {
+                    exit_status = status;
+                }
+            }
+        }
+    }
+
+    // Fetch from all of the configured remotes, including newly added remotes.
+    if params.fetch && !job.remotes.is_empty() {
+        let mut command = vec!["git", "fetch", "--multiple"];
+        for (remote, _) in &job.remotes {
+            command.push(remote.as_str());
+        }
+        if verbose > 1 {
+            print_quoted_command(&command);
+        }
         let exec = cmd::exec_in_dir(&command, path);
         let status = cmd::status(exec.join());
         if status != errors::EX_OK {
@@ -280,129 +942,286 @@ fn update_tree_from_context(
     Ok(exit_status)
 }
 
-/// Use "git worktree" to create a worktree.
-/// Grow the parent worktree first and then create our worktree.
-fn grow_tree_from_context_as_worktree(
-    config: &model::Configuration,
-    configured_worktrees: &mut HashSet<String>,
-    ctx: &model::TreeContext,
-    quiet: bool,
-    verbose: u8,
-) -> Result<i32> {
-    let mut exit_status = errors::EX_OK;
-    let tree = match config.trees.get(&ctx.tree) {
-        Some(tree) => tree,
-        None => return Ok(exit_status),
-    };
-    let worktree = eval::tree_value(
-        config,
-        tree.worktree.get_expr(),
-        &ctx.tree,
-        ctx.garden.as_ref(),
-    );
-    let branch = eval::tree_value(
-        config,
-        tree.branch.get_expr(),
-        &ctx.tree,
-        ctx.garden.as_ref(),
-    );
+/// Synchronize a possibly multi-valued git config setting with the configured values.
+/// Settings with multiple values are replaced using "git config --replace-all" and
+/// "git config --add". Settings without values are removed using "git config --unset-all".
+pub fn update_gitconfig(path: &std::path::Path, name: &str, values: &[String], verbose: u8) -> i32 {
+    // Settings that already have the configured values are left as-is.
+    // Only the repository's configuration is considered, not the global configuration.
+    let command = ["git", "config", "--local", "--get-all", name];
+    let exec = cmd::exec_in_dir(&command, path);
+    if let Ok(capture) = cmd::capture_stdout(exec) {
+        let existing: Vec<String> = if capture.success() {
+            cmd::trim_stdout(&capture)
+                .lines()
+                .map(String::from)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if existing == values {
+            return errors::EX_OK;
+        }
+    }
 
-    let parent_ctx =
-        query::tree_from_name(config, &worktree, ctx.garden.as_ref(), ctx.group.as_ref())
-            .ok_or_else(|| errors::GardenError::WorktreeNotFound {
-                tree: tree.get_name().to_string(),
-                worktree: worktree.clone(),
-            })?;
-
-    exit_status =
-        grow_tree_from_context(config, configured_worktrees, &parent_ctx, quiet, verbose)?;
-    if exit_status != 0 {
-        return Err(errors::GardenError::WorktreeParentCreationError {
-            tree: tree.get_name().into(),
-            worktree,
+    let mut commands = Vec::new();
+    if values.is_empty() {
+        commands.push(vec!["git", "config", "--unset-all", name]);
+    }
+    for (index, value) in values.iter().enumerate() {
+        let mode = if index == 0 { "--replace-all" } else { "--add" };
+        commands.push(vec!["git", "config", mode, name, value.as_str()]);
+    }
+    for command in &commands {
+        if verbose > 1 {
+            print_quoted_command(command);
+        }
+        let exec = cmd::exec_in_dir(command, path);
+        let status = cmd::status(exec.join());
+        // "git config --unset-all" exits with status 5 when the setting does not exist.
+        if status != errors::EX_OK && !(values.is_empty() && status == 5) {
+            return status;
         }
-        .into());
     }
 
-    let tree_path = tree.path_as_ref()?;
-    let parent_tree = match config.trees.get(&parent_ctx.tree) {
-        Some(parent_tree) => parent_tree,
-        None => {
-            return Err(errors::GardenError::WorktreeNotFound {
-                tree: tree.get_name().to_string(),
-                worktree,
+    errors::EX_OK
+}
+
+/// Apply the sparse checkout paths and initialize submodules in a worktree.
+fn update_checkout(job: &GrowJob, verbose: u8) -> i32 {
+    let exit_status = update_sparse_checkout(job, verbose);
+    if exit_status != errors::EX_OK {
+        return exit_status;
+    }
+
+    update_submodules(job, verbose)
+}
+
+/// Configure a cone mode sparse checkout using "git sparse-checkout set".
+/// The sparse checkout is only modified when the configured paths have changed.
+fn update_sparse_checkout(job: &GrowJob, verbose: u8) -> i32 {
+    if job.sparse_checkout.is_empty() || job.is_bare_repository {
+        return errors::EX_OK;
+    }
+    let path = std::path::Path::new(&job.path);
+    let normalize = |sparse_path: &str| sparse_path.trim_matches('/').to_string();
+    let configured: HashSet<String> = job
+        .sparse_checkout
+        .iter()
+        .map(|sparse_path| normalize(sparse_path))
+        .collect();
+
+    // "git sparse-checkout list" fails when the worktree is not sparse.
+    let command = ["git", "sparse-checkout", "list"];
+    let exec = cmd::exec_in_dir(&command, path).stderr(subprocess::NullFile);
+    if let Ok(capture) = cmd::capture_stdout(exec) {
+        if capture.success() {
+            let existing: HashSet<String> =
+                cmd::trim_stdout(&capture).lines().map(normalize).collect();
+            if existing == configured {
+                return errors::EX_OK;
             }
-            .into())
         }
-    };
-    let parent_path = parent_tree.path_as_ref()?;
+    }
 
-    let mut cmd: Vec<&str> = ["git", "worktree", "add"].to_vec();
-    if !branch.is_empty() {
-        cmd.push("--track");
-        cmd.push("-b");
-        cmd.push(&branch);
+    let mut command = vec!["git", "sparse-checkout", "set", "--cone"];
+    for sparse_path in &job.sparse_checkout {
+        command.push(sparse_path.as_str());
+    }
+    if verbose > 1 {
+        print_quoted_command(&command);
     }
+    let exec = cmd::exec_in_dir(&command, path);
 
-    // The parent_path is the base path from which we'll execute "git worktree add".
-    // Compute a relative path to the child.
-    let relative_path_str;
-    if let Some(relative_path) = pathdiff::diff_paths(tree_path, parent_path) {
-        relative_path_str = relative_path.to_string_lossy().to_string();
-        cmd.push(&relative_path_str);
-    } else {
-        cmd.push(tree_path);
+    cmd::status(exec.join())
+}
+
+/// Initialize and update submodules using "git submodule update --init".
+/// Submodules are checked out separately in each worktree.
+fn update_submodules(job: &GrowJob, verbose: u8) -> i32 {
+    let mut command = vec!["git", "submodule", "update", "--init"];
+    match job.submodules {
+        model::Submodules::Disabled => return errors::EX_OK,
+        model::Submodules::Enabled => (),
+        model::Submodules::Recursive => command.push("--recursive"),
     }
+    if job.is_bare_repository {
+        return errors::EX_OK;
+    }
+    if verbose > 1 {
+        print_quoted_command(&command);
+    }
+    let exec = cmd::exec_in_dir(&command, &job.path);
 
-    let remote_branch;
-    if !branch.is_empty() {
-        // TODO: Support tree.<tree>.branches.<branch-name>.upstream
-        // to generalize the remote branch name instead of hard-coding "origin/".
-        remote_branch = format!("origin/{branch}");
-        cmd.push(&remote_branch);
+    cmd::status(exec.join())
+}
+
+/// Check out the tag, commit or ref that a tree is pinned to.
+/// Pinned revisions that are missing are fetched from the "origin" remote.
+/// Tags and commits are checked out with a detached HEAD. Refs that name
+/// a local branch check out the branch. Locked commits that were recorded
+/// alongside a branch check out the branch and reset it to the commit.
+fn checkout_pin(job: &GrowJob, verbose: u8) -> i32 {
+    let pin = match &job.pin {
+        Some(pin) if !job.is_bare_repository => pin,
+        _ => return errors::EX_OK,
+    };
+    let revision = pin.revision();
+    let mut commit = git::rev_parse_commit(&job.path, &revision);
+    if commit.is_none() {
+        if let Some(refspec) = pin.refspec() {
+            let command = ["git", "fetch", "origin", refspec.as_str()];
+            if verbose > 1 {
+                print_quoted_command(&command);
+            }
+            let exec = cmd::exec_in_dir(&command, &job.path);
+            if cmd::status(exec.join()) == errors::EX_OK {
+                commit = git::rev_parse_commit(&job.path, &revision);
+            }
+        }
+    }
+    let commit = match commit {
+        Some(commit) => commit,
+        None => {
+            eprintln!(
+                "{} unable to resolve {} for {}",
+                model::Color::red("error:"),
+                pin,
+                job.tree
+            );
+            return errors::EX_DATAERR;
+        }
+    };
+    if !job.locked_branch.is_empty() {
+        return checkout_locked_branch(job, &commit, verbose);
     }
 
+    let mut command = vec!["git", "checkout", "--quiet"];
+    match pin {
+        model::TreePin::Ref(branch) if is_local_branch(&job.path, branch) => {
+            let command = ["git", "symbolic-ref", "--quiet", "HEAD"];
+            let exec = cmd::exec_in_dir(&command, &job.path);
+            if let Ok(capture) = cmd::capture_stdout(exec) {
+                if cmd::trim_stdout(&capture) == format!("refs/heads/{branch}") {
+                    return errors::EX_OK;
+                }
+            }
+        }
+        _ => {
+            if git::rev_parse_commit(&job.path, "HEAD").as_ref() == Some(&commit) {
+                return errors::EX_OK;
+            }
+            command.push("--detach");
+        }
+    }
+    command.push(revision.as_str());
     if verbose > 1 {
-        print_quoted_command(&cmd);
+        print_quoted_command(&command);
     }
-    let exec = cmd::exec_in_dir(&cmd, parent_path);
-    exit_status = cmd::status(exec.join());
+    let exec = cmd::exec_in_dir(&command, &job.path);
 
-    if exit_status != 0 {
-        return Err(errors::GardenError::WorktreeGitCheckoutError {
-            tree: tree.get_name().clone(),
-            status: exit_status,
+    cmd::status(exec.join())
+}
+
+/// Check out the branch recorded in "garden.lock" and reset it to the locked commit.
+/// The branch is created when it does not exist.
+fn checkout_locked_branch(job: &GrowJob, commit: &str, verbose: u8) -> i32 {
+    let branch = job.locked_branch.as_str();
+    let command = ["git", "symbolic-ref", "--quiet", "HEAD"];
+    let exec = cmd::exec_in_dir(&command, &job.path);
+    if let Ok(capture) = cmd::capture_stdout(exec) {
+        if cmd::trim_stdout(&capture) == format!("refs/heads/{branch}")
+            && git::rev_parse_commit(&job.path, "HEAD").as_deref() == Some(commit)
+        {
+            return errors::EX_OK;
         }
-        .into());
     }
+    let command = ["git", "checkout", "--quiet", "-B", branch, commit];
+    if verbose > 1 {
+        print_quoted_command(&command);
+    }
+    let exec = cmd::exec_in_dir(&command, &job.path);
 
-    Ok(exit_status)
+    cmd::status(exec.join())
+}
+
+/// Return true when the name refers to a local branch.
+fn is_local_branch(path: &str, name: &str) -> bool {
+    let branch_ref = format!("refs/heads/{name}");
+    let command = [
+        "git",
+        "show-ref",
+        "--verify",
+        "--quiet",
+        branch_ref.as_str(),
+    ];
+    let exec = cmd::exec_in_dir(&command, path);
+
+    cmd::status(exec.join()) == errors::EX_OK
+}
+
+/// Fast-forward the current branch to its upstream branch.
+/// Trees with a detached HEAD or a branch without an upstream branch are left as-is.
+/// Trees that cannot be fast-forwarded are recorded and reported after all trees are grown.
+fn fast_forward_tree(job: &GrowJob, state: &GrowState, verbose: u8) -> i32 {
+    if job.is_bare_repository {
+        return errors::EX_OK;
+    }
+    let path = std::path::Path::new(&job.path);
+    let command = ["git", "symbolic-ref", "--quiet", "--short", "HEAD"];
+    let exec = cmd::exec_in_dir(&command, path).stdout(subprocess::NullFile);
+    if cmd::status(exec.join()) != errors::EX_OK {
+        return errors::EX_OK;
+    }
+    let command = ["git", "rev-parse", "--verify", "--quiet", "@{upstream}"];
+    let exec = cmd::exec_in_dir(&command, path).stdout(subprocess::NullFile);
+    if cmd::status(exec.join()) != errors::EX_OK {
+        return errors::EX_OK;
+    }
+
+    let command = ["git", "merge", "--ff-only", "@{upstream}"];
+    if verbose > 1 {
+        print_quoted_command(&command);
+    }
+    let exec = cmd::exec_in_dir(&command, path);
+    let status = cmd::status(exec.join());
+    if status != errors::EX_OK {
+        state
+            .not_updated
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(job.tree.clone());
+    }
+
+    status
 }
 
 /// Initialize a tree symlink entry.
-fn grow_symlink(config: &model::Configuration, ctx: &model::TreeContext) -> Result<i32> {
-    let tree = match config.trees.get(&ctx.tree) {
-        Some(tree) => tree,
-        None => return Ok(errors::EX_OK),
-    };
-    // Invalid usage: non-symlink
-    if !tree.is_symlink || tree.path_as_ref()?.is_empty() || tree.symlink_as_ref()?.is_empty() {
+fn grow_symlink(job: &GrowJob, symlink_str: &str) -> Result<i32> {
+    // Invalid usage: empty symlink
+    if job.path.is_empty() || symlink_str.is_empty() {
         return Err(errors::GardenError::ConfigurationError(format!(
             "invalid symlink: {}",
-            tree.get_name()
+            job.tree
         ))
         .into());
     }
-    let path_str = tree.path_as_ref()?;
-    let path = std::path::PathBuf::from(&path_str);
+    let path = std::path::PathBuf::from(&job.path);
 
     // Leave existing symlinks as-is.
     if std::fs::read_link(&path).is_ok() || path.exists() {
         return Ok(errors::EX_OK);
     }
 
-    let symlink_str = tree.symlink_as_ref()?;
-    let symlink = std::path::PathBuf::from(&symlink_str);
+    create_symlink(&path, symlink_str)?;
+
+    Ok(errors::EX_OK)
+}
+
+/// Create a symlink to the target path. The symlink is created using a relative path
+/// when the target is a child of the symlink's parent directory.
+pub fn create_symlink(path: &std::path::Path, symlink_str: &str) -> Result<()> {
+    let symlink = std::path::PathBuf::from(symlink_str);
 
     // Note: parent directory was already created by the caller.
     let parent = path
@@ -421,7 +1240,7 @@ fn grow_symlink(config: &model::Configuration, ctx: &model::TreeContext) -> Resu
     }
     .to_string();
     let target_path = std::path::PathBuf::from(&target);
-    std::os::unix::fs::symlink(target_path, &path)?;
+    std::os::unix::fs::symlink(target_path, path)?;
 
-    Ok(errors::EX_OK)
+    Ok(())
 }
@@ -1,14 +1,23 @@
 use anyhow::Result;
 use clap::Parser;
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::Yaml;
 
+use super::super::eval;
+use super::super::git;
 use super::super::model;
 use super::super::model::Color;
 use super::super::query;
+use super::super::report;
+use super::super::report::{context_document, yaml_option, yaml_str};
 
 /// Query tree status
 #[derive(Parser, Clone, Debug)]
 #[command(author, about, long_about)]
 pub struct InspectOptions {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = model::OutputFormat::Text)]
+    format: model::OutputFormat,
     /// Tree query for the gardens, groups or trees to inspect
     queries: Vec<String>,
 }
@@ -23,9 +32,65 @@ pub fn main(app: &mut model::ApplicationContext, options: &mut InspectOptions) -
     }
     let verbose = app.options.verbose;
     let config = app.get_root_config_mut();
+    if options.format != model::OutputFormat::Text {
+        let doc = inspect_document(config, &options.queries)?;
+        print!("{}", report::format_document(&doc, options.format));
+        return Ok(());
+    }
     inspect(config, verbose, &options.queries)
 }
 
+/// Build a structured document describing every tree in the evaluated tree queries.
+pub fn inspect_document(config: &model::Configuration, queries: &[String]) -> Result<Yaml> {
+    let mut trees = Vec::new();
+    for query in queries {
+        for context in &query::resolve_trees(config, query) {
+            let tree = match config.trees.get(&context.tree) {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let mut doc = context_document(config, context)?;
+            let exists = std::path::Path::new(tree.path_as_ref()?).exists();
+            doc.insert(yaml_str("exists"), Yaml::Boolean(exists));
+            let symlink = match tree.is_symlink {
+                true => Some(tree.symlink_as_ref()?.as_str()),
+                false => None,
+            };
+            doc.insert(yaml_str("symlink"), yaml_option(symlink));
+            let worktree = match tree.is_worktree {
+                true => Some(eval::tree_value(
+                    config,
+                    tree.worktree.get_expr(),
+                    &context.tree,
+                    context.garden.as_ref(),
+                )),
+                false => None,
+            };
+            doc.insert(yaml_str("worktree"), yaml_option(worktree.as_deref()));
+            let mut commands: Vec<&String> = tree.commands.keys().collect();
+            commands.sort();
+            let commands = commands.into_iter().map(|name| yaml_str(name)).collect();
+            doc.insert(yaml_str("commands"), Yaml::Array(commands));
+            let groups = config
+                .groups
+                .iter()
+                .filter(|(_, group)| {
+                    query::trees_from_group(config, None, group)
+                        .iter()
+                        .any(|ctx| ctx.tree == context.tree)
+                })
+                .map(|(name, _)| yaml_str(name))
+                .collect();
+            doc.insert(yaml_str("groups"), Yaml::Array(groups));
+            trees.push(Yaml::Hash(doc));
+        }
+    }
+    let mut doc = YamlHash::new();
+    doc.insert(yaml_str("trees"), Yaml::Array(trees));
+
+    Ok(Yaml::Hash(doc))
+}
+
 /// Inspect every tree in the evaluated tree query
 pub fn inspect(config: &mut model::Configuration, verbose: u8, queries: &[String]) -> Result<()> {
     for query in queries {
@@ -90,6 +155,30 @@ pub fn inspect(config: &mut model::Configuration, verbose: u8, queries: &[String
                     Color::green(tree.get_name()).bold()
                 );
             }
+
+            // Flag pinned trees whose HEAD differs from the pinned revision.
+            if tree.is_symlink {
+                continue;
+            }
+            if let Some(pin) = eval::tree_pin(config, &context.tree, context.garden.as_ref()) {
+                let head = git::rev_parse_commit(path, "HEAD");
+                let pinned = git::rev_parse_commit(path, &pin.revision());
+                if pinned.is_none() {
+                    println!(
+                        "  {} {} {}",
+                        Color::red("!").bold(),
+                        Color::red("unable to resolve"),
+                        Color::red(&pin)
+                    );
+                } else if head != pinned {
+                    println!(
+                        "  {} {} {}",
+                        Color::yellow("!").bold(),
+                        Color::yellow("HEAD differs from"),
+                        Color::yellow(&pin)
+                    );
+                }
+            }
         }
     }
 
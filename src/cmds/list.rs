@@ -1,5 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::Yaml;
 
 /// List available gardens, groups, trees and commands
 #[derive(Parser, Clone, Debug)]
@@ -8,17 +10,33 @@ pub struct ListOptions {
     /// List commands
     #[arg(long, short)]
     commands: bool,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = model::OutputFormat::Text)]
+    format: model::OutputFormat,
 }
 
 use super::super::model;
+use super::super::report;
+use super::super::report::{yaml_option, yaml_str};
 
 pub fn main(app: &mut model::ApplicationContext, options: &ListOptions) -> Result<()> {
     let config = app.get_root_config_mut();
 
+    if options.format != model::OutputFormat::Text {
+        let doc = list_document(config, options.commands)?;
+        print!("{}", report::format_document(&doc, options.format));
+        return Ok(());
+    }
+
     if options.commands {
         println!("commands:");
         for cmd in config.commands.keys() {
-            println!("- {cmd}");
+            match config.command_settings.get(cmd) {
+                Some(settings) if !settings.description.is_empty() => {
+                    println!("- {cmd}: {}", settings.description);
+                }
+                _ => println!("- {cmd}"),
+            }
         }
         return Ok(());
     }
@@ -52,3 +70,55 @@ pub fn main(app: &mut model::ApplicationContext, options: &ListOptions) -> Resul
 
     Ok(())
 }
+
+/// Build a structured document describing the gardens, groups, trees and commands.
+/// Only the commands are included when "commands_only" is true.
+fn list_document(config: &model::Configuration, commands_only: bool) -> Result<Yaml> {
+    let mut doc = YamlHash::new();
+    let names = |names: &mut dyn Iterator<Item = &String>| {
+        Yaml::Array(names.map(|name| yaml_str(name)).collect())
+    };
+
+    if !commands_only {
+        let mut gardens = YamlHash::new();
+        for (name, garden) in &config.gardens {
+            let mut entry = YamlHash::new();
+            entry.insert(yaml_str("groups"), names(&mut garden.groups.iter()));
+            entry.insert(yaml_str("trees"), names(&mut garden.trees.iter()));
+            gardens.insert(yaml_str(name), Yaml::Hash(entry));
+        }
+        doc.insert(yaml_str("gardens"), Yaml::Hash(gardens));
+
+        let mut groups = YamlHash::new();
+        for (name, group) in &config.groups {
+            groups.insert(yaml_str(name), names(&mut group.members.iter()));
+        }
+        doc.insert(yaml_str("groups"), Yaml::Hash(groups));
+
+        let mut trees = YamlHash::new();
+        for (name, tree) in &config.trees {
+            let mut entry = YamlHash::new();
+            entry.insert(yaml_str("path"), yaml_str(tree.path_as_ref()?));
+            trees.insert(yaml_str(name), Yaml::Hash(entry));
+        }
+        doc.insert(yaml_str("trees"), Yaml::Hash(trees));
+    }
+
+    let mut commands = YamlHash::new();
+    let mut command_names: Vec<&String> = config.commands.keys().collect();
+    command_names.sort();
+    for name in command_names {
+        let description = match config.command_settings.get(name) {
+            Some(settings) if !settings.description.is_empty() => {
+                Some(settings.description.as_str())
+            }
+            _ => None,
+        };
+        let mut entry = YamlHash::new();
+        entry.insert(yaml_str("description"), yaml_option(description));
+        commands.insert(yaml_str(name), Yaml::Hash(entry));
+    }
+    doc.insert(yaml_str("commands"), Yaml::Hash(commands));
+
+    Ok(Yaml::Hash(doc))
+}
@@ -0,0 +1,188 @@
+use super::super::cmd;
+use super::super::config;
+use super::super::errors;
+use super::super::eval;
+use super::super::git;
+use super::super::model;
+use super::super::query;
+
+use anyhow::Result;
+use clap::Parser;
+use indexmap::IndexMap;
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::Yaml;
+
+/// Record the current commit, branch and remote URL of trees in "garden.lock"
+#[derive(Parser, Clone, Debug)]
+#[command(author, about, long_about)]
+pub struct LockOptions {
+    /// Tree queries for the trees to lock [default: all trees]
+    queries: Vec<String>,
+}
+
+/// LockedTree is the state of a tree recorded in "garden.lock".
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LockedTree {
+    /// The commit checked out in the tree.
+    pub commit: String,
+    /// The branch checked out in the tree. Empty when HEAD is detached.
+    pub branch: String,
+    /// The URL of the "origin" remote.
+    pub url: String,
+}
+
+/// LockedTrees maps tree names to their locked state.
+pub type LockedTrees = IndexMap<model::TreeName, LockedTree>;
+
+/// Main entry point for the "garden lock" command
+pub fn main(app: &mut model::ApplicationContext, options: &LockOptions) -> Result<()> {
+    let verbose = app.options.verbose;
+    let config = app.get_root_config();
+    let path = lock_path(config)?;
+
+    // Existing entries are retained so that a subset of trees can be re-locked.
+    let mut locked = if path.exists() {
+        read(&path)?
+    } else {
+        LockedTrees::new()
+    };
+
+    let mut contexts = Vec::new();
+    if options.queries.is_empty() {
+        for tree in config.trees.keys() {
+            contexts.push(model::TreeContext::new(tree, config.get_id(), None, None));
+        }
+    }
+    for query in &options.queries {
+        contexts.append(&mut query::resolve_trees(config, query));
+    }
+    for ctx in &contexts {
+        let tree = match config.trees.get(&ctx.tree) {
+            Some(tree) if !tree.is_symlink => tree,
+            _ => continue,
+        };
+        // Missing trees are not locked.
+        let tree_path = tree.path_as_ref()?;
+        if !std::path::Path::new(tree_path).exists() {
+            continue;
+        }
+        let commit = match git::rev_parse_commit(tree_path, "HEAD") {
+            Some(commit) => commit,
+            None => continue,
+        };
+        let url = match origin_url(tree_path) {
+            Some(url) => url,
+            None => match tree.remotes.get("origin") {
+                Some(remote) => {
+                    eval::tree_value(config, remote.get_expr(), &ctx.tree, ctx.garden.as_ref())
+                }
+                None => String::new(),
+            },
+        };
+        let locked_tree = LockedTree {
+            commit,
+            branch: git::current_branch(tree_path).unwrap_or_default(),
+            url,
+        };
+        if verbose > 0 {
+            println!(
+                "{} {} {}",
+                model::Color::green("+"),
+                model::Color::green(&ctx.tree).bold(),
+                model::Color::blue(&locked_tree.commit)
+            );
+        }
+        locked.insert(ctx.tree.clone(), locked_tree);
+    }
+
+    write(&path, &locked)?;
+
+    Ok(())
+}
+
+/// Return the path to "garden.lock". The lock file is stored next to the garden file.
+pub fn lock_path(config: &model::Configuration) -> Result<std::path::PathBuf, errors::GardenError> {
+    let config_path = config.get_path()?;
+    let dirname = config_path
+        .parent()
+        .map(std::path::Path::to_path_buf)
+        .unwrap_or_default();
+
+    Ok(dirname.join("garden.lock"))
+}
+
+/// Read locked trees from a lock file.
+pub fn read(path: &std::path::Path) -> Result<LockedTrees, errors::GardenError> {
+    let doc = config::reader::read_yaml(path)?;
+    let mut locked = LockedTrees::new();
+    let trees = match &doc["trees"] {
+        Yaml::Hash(trees) => trees,
+        Yaml::BadValue => return Ok(locked),
+        _ => {
+            return Err(errors::GardenError::ConfigurationError(format!(
+                "invalid trees in {}",
+                path.display()
+            )))
+        }
+    };
+    let get_str = |value: &Yaml| value.as_str().unwrap_or_default().to_string();
+    for (name, value) in trees {
+        let name = match name.as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let locked_tree = LockedTree {
+            commit: get_str(&value["commit"]),
+            branch: get_str(&value["branch"]),
+            url: get_str(&value["url"]),
+        };
+        if locked_tree.commit.is_empty() {
+            return Err(errors::GardenError::ConfigurationError(format!(
+                "missing commit for {name} in {}",
+                path.display()
+            )));
+        }
+        locked.insert(name, locked_tree);
+    }
+
+    Ok(locked)
+}
+
+/// Write locked trees to a lock file.
+pub fn write(path: &std::path::Path, locked: &LockedTrees) -> Result<(), errors::GardenError> {
+    let mut trees = YamlHash::new();
+    for (name, locked_tree) in locked {
+        let mut entry = YamlHash::new();
+        entry.insert(
+            Yaml::String("commit".into()),
+            Yaml::String(locked_tree.commit.clone()),
+        );
+        if !locked_tree.branch.is_empty() {
+            entry.insert(
+                Yaml::String("branch".into()),
+                Yaml::String(locked_tree.branch.clone()),
+            );
+        }
+        if !locked_tree.url.is_empty() {
+            entry.insert(
+                Yaml::String("url".into()),
+                Yaml::String(locked_tree.url.clone()),
+            );
+        }
+        trees.insert(Yaml::String(name.clone()), Yaml::Hash(entry));
+    }
+    let mut doc = YamlHash::new();
+    doc.insert(Yaml::String("trees".into()), Yaml::Hash(trees));
+
+    config::writer::write_yaml(&Yaml::Hash(doc), path)
+}
+
+/// Return the URL of the "origin" remote for the repository at the specified path.
+fn origin_url(path: &str) -> Option<String> {
+    let command = ["git", "config", "--get", "remote.origin.url"];
+    let exec = cmd::exec_in_dir(&command, path);
+    match cmd::capture(exec) {
+        Ok(capture) if capture.success() => Some(cmd::trim_stdout(&capture)),
+        _ => None,
+    }
+}
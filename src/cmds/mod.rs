@@ -1,3 +1,9 @@
+/// Cache command
+pub mod cache;
+
+/// Check command
+pub mod check;
+
 /// Configuration-defined commands
 pub mod cmd;
 
@@ -22,11 +28,20 @@ pub mod inspect;
 /// List command
 pub mod list;
 
+/// Lock command
+pub mod lock;
+
 /// Plant command
 pub mod plant;
 
 /// Prune command
 pub mod prune;
 
+/// Query command
+pub mod query;
+
 /// Shell command
 pub mod shell;
+
+/// Status command
+pub mod status;
@@ -253,6 +253,14 @@ fn plant_path(
         }
     }
 
+    // Update the "submodules" field. Existing "submodules: recursive" entries are retained.
+    {
+        let submodules_key = Yaml::String("submodules".into());
+        if path.join(".gitmodules").exists() && !entry.contains_key(&submodules_key) {
+            entry.insert(submodules_key, Yaml::Boolean(true));
+        }
+    }
+
     // Move the entry into the trees container
     if let Some(tree_entry) = trees.get_mut(&key) {
         *tree_entry = Yaml::Hash(entry);
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use clap::Parser;
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::Yaml;
+
+use super::super::model;
+use super::super::query;
+use super::super::report;
+
+/// Resolve tree queries into trees
+#[derive(Parser, Clone, Debug)]
+#[command(author, about, long_about)]
+pub struct QueryOptions {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = model::OutputFormat::Text)]
+    format: model::OutputFormat,
+    /// Tree query for the gardens, groups or trees to resolve
+    #[arg(required = true)]
+    queries: Vec<String>,
+}
+
+/// Main entry point for the "garden query" command
+pub fn main(app: &mut model::ApplicationContext, options: &QueryOptions) -> Result<()> {
+    let config = app.get_root_config();
+    let mut contexts = Vec::new();
+    for query in &options.queries {
+        contexts.append(&mut query::resolve_trees(config, query));
+    }
+
+    if options.format == model::OutputFormat::Text {
+        for ctx in &contexts {
+            println!("{}", ctx.tree);
+        }
+        return Ok(());
+    }
+
+    let mut trees = Vec::new();
+    for ctx in &contexts {
+        trees.push(Yaml::Hash(report::context_document(config, ctx)?));
+    }
+    let mut doc = YamlHash::new();
+    doc.insert(report::yaml_str("trees"), Yaml::Array(trees));
+    print!(
+        "{}",
+        report::format_document(&Yaml::Hash(doc), options.format)
+    );
+
+    Ok(())
+}
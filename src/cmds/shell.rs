@@ -63,7 +63,13 @@ pub fn main(app: &mut model::ApplicationContext, options: &ShellOptions) -> Resu
 
     if let Some(value) = shlex::split(&shell) {
         cmd::exec_in_context(
-            config, &context, /*quiet*/ true, /*verbose*/ 0, &value,
+            config,
+            &context,
+            /*quiet*/ true,
+            /*verbose*/ 0,
+            model::OutputMode::Inherit,
+            /*dry_run*/ false,
+            &value,
         )
         .map_err(|err| err.into())
     } else {
@@ -0,0 +1,221 @@
+use super::super::cmd;
+use super::super::eval;
+use super::super::git;
+use super::super::model;
+use super::super::model::Color;
+use super::super::query;
+
+use anyhow::Result;
+use clap::Parser;
+use rayon::prelude::*;
+
+/// Report the Git status of trees
+#[derive(Parser, Clone, Debug)]
+#[command(author, about, long_about)]
+pub struct StatusOptions {
+    /// Number of trees to query in parallel. "0" uses all available cores
+    #[arg(short = 'j', long = "jobs", default_value_t = 0)]
+    num_jobs: usize,
+    /// Tree query for the gardens, groups or trees to report
+    queries: Vec<String>,
+}
+
+/// StatusJob contains the evaluated settings needed to query a single tree.
+/// Trees are evaluated on the main thread so that Git can be queried in parallel.
+#[derive(Clone, Debug)]
+struct StatusJob {
+    /// Name of the tree.
+    tree: String,
+    /// Path to the tree.
+    path: String,
+    /// The configured branch.
+    branch: String,
+    /// The symlink target for symlink trees.
+    symlink: Option<String>,
+}
+
+/// TreeStatus is the state of a tree queried from Git.
+#[derive(Clone, Debug)]
+enum TreeStatus {
+    /// The tree does not exist.
+    Missing,
+    /// The tree is a symlink to another path.
+    Symlink(String),
+    /// The tree is a Git repository. Details are unavailable when Git commands fail.
+    Repository {
+        details: Option<model::GitTreeDetails>,
+        status: Option<model::GitStatus>,
+    },
+}
+
+/// Main entry point for the "garden status" command
+pub fn main(app: &mut model::ApplicationContext, options: &StatusOptions) -> Result<()> {
+    let mut queries = options.queries.clone();
+    if queries.is_empty() {
+        queries.push(".".into());
+    }
+    if app.options.debug_level("status") > 0 {
+        debug!("queries: {:?}", queries);
+    }
+    let verbose = app.options.verbose;
+    let config = app.get_root_config();
+    status(config, verbose, options.num_jobs, &queries)
+}
+
+/// Report the status of every tree in the evaluated tree queries.
+pub fn status(
+    config: &model::Configuration,
+    verbose: u8,
+    num_jobs: usize,
+    queries: &[String],
+) -> Result<()> {
+    let mut jobs = Vec::new();
+    for query in queries {
+        for ctx in &query::resolve_trees(config, query) {
+            let tree = match config.trees.get(&ctx.tree) {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let symlink = match tree.is_symlink {
+                true => Some(tree.symlink_as_ref()?.clone()),
+                false => None,
+            };
+            jobs.push(StatusJob {
+                tree: ctx.tree.clone(),
+                path: tree.path_as_ref()?.clone(),
+                branch: eval::tree_value(
+                    config,
+                    tree.branch.get_expr(),
+                    &ctx.tree,
+                    ctx.garden.as_ref(),
+                ),
+                symlink,
+            });
+        }
+    }
+
+    cmd::initialize_threads(num_jobs);
+    let statuses: Vec<TreeStatus> = jobs.par_iter().map(tree_status).collect();
+    for (job, tree_status) in jobs.iter().zip(statuses) {
+        println!("{}", format_status(job, &tree_status, verbose));
+    }
+
+    Ok(())
+}
+
+/// Query the status of a tree from Git.
+fn tree_status(job: &StatusJob) -> TreeStatus {
+    let pathbuf = std::path::PathBuf::from(&job.path);
+    if let Some(target) = &job.symlink {
+        if std::fs::read_link(&pathbuf).is_ok() {
+            return TreeStatus::Symlink(target.clone());
+        }
+    }
+    if !pathbuf.exists() {
+        return TreeStatus::Missing;
+    }
+    let details = git::worktree_details(&pathbuf).ok();
+    let status = match &details {
+        Some(details) if details.tree_type == model::GitTreeType::Bare => None,
+        _ => git::status(&job.path).ok(),
+    };
+
+    TreeStatus::Repository { details, status }
+}
+
+/// Format a single line of status output for a tree.
+fn format_status(job: &StatusJob, tree_status: &TreeStatus, verbose: u8) -> String {
+    let path = if verbose > 0 {
+        format!("  {}", Color::green(&job.path))
+    } else {
+        String::new()
+    };
+    let (details, status) = match tree_status {
+        TreeStatus::Missing => {
+            let path = if verbose > 0 {
+                format!("  {}", Color::red(&job.path).dimmed())
+            } else {
+                String::new()
+            };
+            return format!(
+                "{} {}{}  {}",
+                Color::red("-").dimmed(),
+                Color::red(&job.tree),
+                path,
+                Color::red("(missing)").dimmed()
+            );
+        }
+        TreeStatus::Symlink(target) => {
+            return format!(
+                "{} {}{} {} {}",
+                Color::green("+"),
+                Color::green(&job.tree).bold(),
+                path,
+                Color::yellow("->").bold(),
+                Color::blue(target).bold()
+            );
+        }
+        TreeStatus::Repository { details, status } => (details, status),
+    };
+
+    let mut line = format!(
+        "{} {}{}",
+        Color::green("+"),
+        Color::green(&job.tree).bold(),
+        path
+    );
+    if let Some(status) = status {
+        // The current branch is highlighted when it differs from the configured branch.
+        let branch = if status.branch.is_empty() {
+            Color::yellow("(detached)".to_string())
+        } else if !job.branch.is_empty() && job.branch != status.branch {
+            Color::yellow(status.branch.clone())
+        } else {
+            Color::blue(status.branch.clone())
+        };
+        line += &format!("  {branch}");
+        if !job.branch.is_empty() && job.branch != status.branch {
+            line += &format!(
+                " {}",
+                Color::yellow(format!("(configured: {})", job.branch))
+            );
+        }
+        let mut tracking = Vec::new();
+        if status.ahead > 0 {
+            tracking.push(format!("ahead {}", status.ahead));
+        }
+        if status.behind > 0 {
+            tracking.push(format!("behind {}", status.behind));
+        }
+        if !tracking.is_empty() {
+            line += &format!(" {}", Color::cyan(format!("[{}]", tracking.join(", "))));
+        }
+        let mut counts = Vec::new();
+        if status.dirty > 0 {
+            counts.push(format!("dirty {}", status.dirty));
+        }
+        if status.untracked > 0 {
+            counts.push(format!("untracked {}", status.untracked));
+        }
+        if status.stashes > 0 {
+            counts.push(format!("stashes {}", status.stashes));
+        }
+        if !counts.is_empty() {
+            line += &format!(" {}", Color::red(counts.join(", ")));
+        }
+    }
+    match details.as_ref().map(|details| &details.tree_type) {
+        Some(model::GitTreeType::Bare) => {
+            line += &format!(" {}", Color::cyan("(bare)"));
+        }
+        Some(model::GitTreeType::Worktree(parent)) => {
+            line += &format!(" {}", Color::cyan(format!("(worktree of {parent})")));
+        }
+        None => {
+            line += &format!(" {}", Color::red("(not a git repository)"));
+        }
+        _ => (),
+    }
+
+    line
+}
@@ -65,6 +65,15 @@ fn parse_recursive(
         debug!("yaml: garden.shell = {}", config.shell);
     }
 
+    // garden.cache
+    if get_variable(&doc["garden"]["cache"], &mut config.cache) && config_verbose > 0 {
+        debug!("yaml: garden.cache = {}", config.cache.get_expr());
+    }
+    get_bool(
+        &doc["garden"]["cache-dissociate"],
+        &mut config.cache_dissociate,
+    );
+
     // GARDEN_ROOT and GARDEN_CONFIG_DIR are relative to the root configuration.
     // Referencing these variables from garden files included using garden.includes
     // resolves to the root config's location, not the included location.
@@ -141,12 +150,18 @@ fn parse_recursive(
     }
 
     get_multivariables(&doc["environment"], &mut config.environment);
+    get_gitconfig(&doc["gitconfig"], &mut config.gitconfig);
 
     // commands
     if config_verbose > 1 {
         debug!("yaml: commands");
     }
-    if !get_multivariables_hashmap(&doc["commands"], &mut config.commands) && config_verbose > 1 {
+    if !get_commands(
+        &doc["commands"],
+        &mut config.commands,
+        &mut config.command_settings,
+    ) && config_verbose > 1
+    {
         debug!("yaml: no commands");
     }
 
@@ -250,6 +265,16 @@ fn get_i64(yaml: &Yaml, value: &mut i64) -> bool {
     }
 }
 
+/// Yaml -> Option<u64> for non-negative integer values.
+fn get_u64(yaml: &Yaml) -> Option<u64> {
+    let mut value: i64 = 0;
+    if get_i64(yaml, &mut value) && value >= 0 {
+        Some(value as u64)
+    } else {
+        None
+    }
+}
+
 /// Yaml -> bool
 fn get_bool(yaml: &Yaml, value: &mut bool) -> bool {
     match yaml {
@@ -261,6 +286,42 @@ fn get_bool(yaml: &Yaml, value: &mut bool) -> bool {
     }
 }
 
+/// Read branch settings. Strings are shorthand for the "upstream" branch.
+fn get_branches(yaml: &Yaml, branches: &mut model::BranchSettingsHashMap) -> bool {
+    let hash = match yaml {
+        Yaml::Hash(hash) => hash,
+        _ => return false,
+    };
+    for (name, value) in hash {
+        let name = match name.as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let mut settings = model::BranchSettings::default();
+        if !get_variable(value, &mut settings.upstream) {
+            get_variable(&value["upstream"], &mut settings.upstream);
+            get_variable(&value["base"], &mut settings.base);
+        }
+        branches.insert(name, settings);
+    }
+
+    true
+}
+
+/// Yaml::Boolean or "recursive" -> model::Submodules
+fn get_submodules(yaml: &Yaml, submodules: &mut model::Submodules) -> bool {
+    match yaml {
+        Yaml::Boolean(true) => *submodules = model::Submodules::Enabled,
+        Yaml::Boolean(false) => *submodules = model::Submodules::Disabled,
+        Yaml::String(value) if value == "recursive" => {
+            *submodules = model::Submodules::Recursive;
+        }
+        _ => return false,
+    }
+
+    true
+}
+
 /// Yaml::String or Yaml::Array<Yaml::String> -> Vec<String>
 fn get_indexset_str(yaml: &Yaml, values: &mut IndexSet<String>) -> bool {
     match yaml {
@@ -310,6 +371,47 @@ fn get_variable(yaml: &Yaml, value: &mut model::Variable) -> bool {
     }
 }
 
+/// Read "gitconfig" settings into a MultiVariableHashMap.
+/// Lists are multi-valued settings. Null values and empty lists unset settings.
+fn get_gitconfig(yaml: &Yaml, hashmap: &mut model::MultiVariableHashMap) -> bool {
+    let hash = match yaml {
+        Yaml::Hash(hash) => hash,
+        _ => return false,
+    };
+    let get_value = |value: &Yaml| -> Option<model::Variable> {
+        match value {
+            // Defer resolution of string values.
+            Yaml::String(yaml_str) => Some(model::Variable::new(yaml_str.clone(), None)),
+            // Integers and booleans are already resolved.
+            Yaml::Integer(_) | Yaml::Boolean(_) => {
+                let scalar = yaml_scalar(value)?;
+                Some(model::Variable::new(scalar.clone(), Some(scalar)))
+            }
+            _ => None,
+        }
+    };
+    for (k, v) in hash {
+        let key = match k.as_str() {
+            Some(key_value) => key_value.to_string(),
+            None => continue,
+        };
+        let values = match v {
+            Yaml::Null => Vec::new(),
+            Yaml::Array(yaml_array) => yaml_array.iter().filter_map(get_value).collect(),
+            _ => match get_value(v) {
+                Some(variable) => vec![variable],
+                None => {
+                    dump_node(v, 1, "");
+                    error!("invalid gitconfig");
+                }
+            },
+        };
+        hashmap.insert(key, values);
+    }
+
+    true
+}
+
 /// Read variable definitions from a yaml::HashMap into a VariablesHashMap
 fn get_variables_hashmap(yaml: &Yaml, hashmap: &mut model::VariableHashMap) -> bool {
     match yaml {
@@ -417,47 +519,160 @@ fn get_multivariables(yaml: &Yaml, vec: &mut Vec<model::MultiVariable>) -> bool
     false
 }
 
-/// Read a mapping of String to Vec of Variables into a MultiVariableHashMap
-fn get_multivariables_hashmap(
+/// Read a String or Array of Strings into a Vec of Variables
+fn get_multivariable_values(yaml: &Yaml) -> Option<Vec<model::Variable>> {
+    match yaml {
+        Yaml::String(yaml_str) => Some(vec![model::Variable::new(yaml_str.to_string(), None)]),
+        Yaml::Array(yaml_array) => {
+            let mut variables = Vec::new();
+            for value in yaml_array {
+                if let Yaml::String(yaml_str) = value {
+                    variables.push(model::Variable::new(yaml_str.clone(), None));
+                }
+            }
+            Some(variables)
+        }
+        Yaml::Integer(yaml_int) => {
+            let value = yaml_int.to_string();
+            Some(vec![model::Variable::new(value.clone(), Some(value))])
+        }
+        _ => {
+            dump_node(yaml, 1, "");
+            error!("invalid variables");
+        }
+    }
+}
+
+/// Read command definitions into a MultiVariableHashMap and CommandSettingsHashMap.
+/// Commands are a string, a list of strings, or a mapping with a "run" entry
+/// alongside optional "shell", "cwd", "env", "description" and "confirm" settings.
+fn get_commands(
     yaml: &Yaml,
-    multivariables: &mut model::MultiVariableHashMap,
+    commands: &mut model::MultiVariableHashMap,
+    command_settings: &mut model::CommandSettingsHashMap,
 ) -> bool {
-    match yaml {
-        Yaml::Hash(hash) => {
-            for (k, v) in hash {
-                let key = match k.as_str() {
-                    Some(key_value) => key_value.to_string(),
-                    None => continue,
-                };
-                match v {
-                    Yaml::String(yaml_str) => {
-                        let variables = vec![model::Variable::new(yaml_str.to_string(), None)];
-                        multivariables.insert(key, variables);
-                    }
-                    Yaml::Array(yaml_array) => {
-                        let mut variables = Vec::new();
-                        for value in yaml_array {
-                            if let Yaml::String(yaml_str) = value {
-                                variables.push(model::Variable::new(yaml_str.clone(), None));
-                            }
-                        }
-                        multivariables.insert(key, variables);
-                    }
-                    Yaml::Integer(yaml_int) => {
-                        let value = yaml_int.to_string();
-                        let variables = vec![model::Variable::new(value.clone(), Some(value))];
-                        multivariables.insert(key, variables);
-                    }
-                    _ => {
-                        dump_node(v, 1, "");
-                        error!("invalid variables");
-                    }
+    let hash = match yaml {
+        Yaml::Hash(hash) => hash,
+        _ => return false,
+    };
+    for (k, v) in hash {
+        let key = match k.as_str() {
+            Some(key_value) => key_value.to_string(),
+            None => continue,
+        };
+        let mut settings = model::CommandSettings::default();
+        let variables = match v {
+            Yaml::Hash(_) => {
+                get_command_settings(v, &mut settings);
+                // Commands that only declare dependencies or hooks do not need "run".
+                match &v["run"] {
+                    Yaml::BadValue => Some(Vec::new()),
+                    run => get_multivariable_values(run),
                 }
             }
+            _ => get_multivariable_values(v),
+        };
+        if let Some(variables) = variables {
+            commands.insert(key.clone(), variables);
+        }
+        // Settings are always recorded so that a command that overrides
+        // a structured command from a template does not inherit its settings.
+        command_settings.insert(key, settings);
+    }
 
-            true
+    true
+}
+
+/// Read the settings for a command defined using the mapping syntax.
+fn get_command_settings(yaml: &Yaml, settings: &mut model::CommandSettings) {
+    get_str(&yaml["shell"], &mut settings.shell);
+    get_variable(&yaml["cwd"], &mut settings.cwd);
+    get_str(&yaml["description"], &mut settings.description);
+    get_bool(&yaml["confirm"], &mut settings.confirm);
+    settings.timeout = get_u64(&yaml["timeout"]);
+    settings.retries = get_u64(&yaml["retries"]);
+    settings.retry_delay = get_u64(&yaml["retry-delay"]);
+    get_vec_variables(&yaml["when"]["exists"], &mut settings.when.exists);
+    get_vec_variables(&yaml["when"]["nonempty"], &mut settings.when.nonempty);
+    get_vec_variables(&yaml["when"]["exec"], &mut settings.when.exec);
+    get_indexset_str(&yaml["depends"], &mut settings.depends);
+    get_indexset_str(&yaml["pre"], &mut settings.pre);
+    get_indexset_str(&yaml["post"], &mut settings.post);
+    get_command_parameters(&yaml["params"], &mut settings.parameters);
+
+    if let Yaml::Hash(hash) = &yaml["env"] {
+        for (k, v) in hash {
+            let name = match k.as_str() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let expr = match v {
+                Yaml::String(yaml_str) => yaml_str.clone(),
+                Yaml::Integer(yaml_int) => yaml_int.to_string(),
+                Yaml::Boolean(yaml_bool) => bool_to_string(*yaml_bool),
+                _ => {
+                    dump_node(v, 1, "");
+                    error!("invalid environment");
+                }
+            };
+            settings
+                .environment
+                .push(model::NamedVariable::new(name, expr, None));
         }
-        _ => false,
+    }
+}
+
+/// Read the "params" declared by a command.
+/// Parameters are either a mapping of settings or a scalar default value.
+fn get_command_parameters(yaml: &Yaml, parameters: &mut Vec<model::CommandParameter>) {
+    let hash = match yaml {
+        Yaml::Hash(hash) => hash,
+        _ => return,
+    };
+    for (k, v) in hash {
+        let mut parameter = model::CommandParameter::default();
+        if !get_str(k, &mut parameter.name) {
+            continue;
+        }
+        match v {
+            Yaml::Hash(_) => {
+                get_str(&v["help"], &mut parameter.help);
+                parameter.kind = match v["type"].as_str() {
+                    Some("bool") | Some("boolean") => model::ParameterType::Bool,
+                    Some("int") | Some("integer") => model::ParameterType::Integer,
+                    Some("string") | None => model::ParameterType::String,
+                    Some(kind) => {
+                        error!("invalid type for parameter {}: {}", parameter.name, kind);
+                    }
+                };
+                parameter.default = yaml_scalar(&v["default"]);
+                if let Some(short) = v["short"].as_str() {
+                    parameter.short = short.chars().next();
+                }
+            }
+            Yaml::Boolean(_) => {
+                parameter.kind = model::ParameterType::Bool;
+                parameter.default = yaml_scalar(v);
+            }
+            Yaml::Integer(_) => {
+                parameter.kind = model::ParameterType::Integer;
+                parameter.default = yaml_scalar(v);
+            }
+            _ => {
+                parameter.default = yaml_scalar(v);
+            }
+        }
+        parameters.push(parameter);
+    }
+}
+
+/// Yaml scalar -> Option<String>
+fn yaml_scalar(yaml: &Yaml) -> Option<String> {
+    match yaml {
+        Yaml::String(yaml_str) => Some(yaml_str.clone()),
+        Yaml::Integer(yaml_int) => Some(yaml_int.to_string()),
+        Yaml::Boolean(yaml_bool) => Some(bool_to_string(*yaml_bool)),
+        _ => None,
     }
 }
 
@@ -633,19 +848,38 @@ fn get_tree_from_url(name: &Yaml, url: &str) -> model::Tree {
 #[inline]
 fn get_tree_fields(value: &Yaml, tree: &mut model::Tree) {
     get_variables_hashmap(&value["variables"], &mut tree.variables);
-    get_variables_hashmap(&value["gitconfig"], &mut tree.gitconfig);
+    get_gitconfig(&value["gitconfig"], &mut tree.gitconfig);
     get_str_variables_hashmap(&value["remotes"], &mut tree.remotes);
 
     get_multivariables(&value["environment"], &mut tree.environment);
-    get_multivariables_hashmap(&value["commands"], &mut tree.commands);
+    get_commands(
+        &value["commands"],
+        &mut tree.commands,
+        &mut tree.command_settings,
+    );
 
     get_variable(&value["branch"], &mut tree.branch);
+    get_variable(&value["tag"], &mut tree.tag);
+    get_variable(&value["commit"], &mut tree.commit);
+    get_variable(&value["ref"], &mut tree.git_ref);
+    get_variable(&value["upstream"], &mut tree.upstream);
+    get_variable(&value["base"], &mut tree.base);
+    get_branches(&value["branches"], &mut tree.branches);
     get_variable(&value["symlink"], &mut tree.symlink);
     get_variable(&value["worktree"], &mut tree.worktree);
 
     get_i64(&value["depth"], &mut tree.clone_depth);
+    get_variable(&value["filter"], &mut tree.clone_filter);
+    // Sparse checkout paths replace the paths from templates and base trees.
+    let mut sparse_checkout = Vec::new();
+    if get_vec_variables(&value["sparse"], &mut sparse_checkout) {
+        tree.sparse_checkout = sparse_checkout;
+    }
     get_bool(&value["bare"], &mut tree.is_bare_repository);
     get_bool(&value["single-branch"], &mut tree.is_single_branch);
+    get_bool(&value["remotes-exclusive"], &mut tree.is_remotes_exclusive);
+    get_indexset_str(&value["remotes-keep"], &mut tree.remotes_keep);
+    get_submodules(&value["submodules"], &mut tree.submodules);
 
     tree.update_flags();
 }
@@ -786,10 +1020,14 @@ fn get_gardens(yaml: &Yaml, gardens: &mut IndexMap<String, model::Garden>) -> bo
                 get_str(name, garden.get_name_mut());
                 get_indexset_str(&value["groups"], &mut garden.groups);
                 get_indexset_str(&value["trees"], &mut garden.trees);
-                get_variables_hashmap(&value["gitconfig"], &mut garden.gitconfig);
+                get_gitconfig(&value["gitconfig"], &mut garden.gitconfig);
                 get_variables_hashmap(&value["variables"], &mut garden.variables);
                 get_multivariables(&value["environment"], &mut garden.environment);
-                get_multivariables_hashmap(&value["commands"], &mut garden.commands);
+                get_commands(
+                    &value["commands"],
+                    &mut garden.commands,
+                    &mut garden.command_settings,
+                );
                 gardens.insert(garden.get_name().to_string(), garden);
             }
             true
@@ -86,6 +86,12 @@ pub enum GardenError {
 
     #[error("unable to write configuration: {path:?}")]
     WriteConfigurationError { path: std::path::PathBuf },
+
+    #[error("unable to write {path:?}: {err}")]
+    WriteFile {
+        path: std::path::PathBuf,
+        err: std::io::Error,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -106,6 +112,14 @@ pub const EX_CANTCREAT: i32 = 73;
 pub const EX_IOERR: i32 = 74;
 pub const EX_CONFIG: i32 = 78;
 
+/// Commands that are killed after exceeding their timeout exit with the
+/// same status as timeout(1).
+pub const EX_TIMEOUT: i32 = 124;
+
+/// Status recorded for commands that did not run because a command that they
+/// require has failed. This is never used as a process exit status.
+pub const EX_SKIPPED: i32 = -1;
+
 impl std::convert::From<GardenError> for i32 {
     fn from(garden_err: GardenError) -> Self {
         match garden_err {
@@ -133,6 +147,7 @@ impl std::convert::From<GardenError> for i32 {
             GardenError::WorktreeParentNotPlantedError { .. } => EX_CONFIG,
             GardenError::WorktreeNotFound { .. } => EX_CONFIG,
             GardenError::WriteConfigurationError { .. } => EX_CANTCREAT,
+            GardenError::WriteFile { .. } => EX_CANTCREAT,
         }
     }
 }
@@ -1,4 +1,5 @@
 use super::cmd;
+use super::errors;
 use super::model;
 use super::path;
 use super::query;
@@ -6,6 +7,8 @@ use super::syntax;
 
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
+
 /// Expand variables across all scopes (garden, tree, and global).
 /// - `config`: reference to Configuration
 /// - `tree_idx`: index into the tree being evaluated
@@ -203,6 +206,72 @@ pub fn tree_value_for_shell(
     }
 }
 
+/// Evaluate the "commit", "tag" or "ref" that a tree is pinned to.
+/// A commit takes precedence over a tag, and a tag takes precedence over a ref.
+pub fn tree_pin(
+    config: &model::Configuration,
+    tree_name: &str,
+    garden_name: Option<&model::GardenName>,
+) -> Option<model::TreePin> {
+    let tree = config.trees.get(tree_name)?;
+    let evaluate = |var: &model::Variable| -> Option<String> {
+        if var.is_empty() {
+            return None;
+        }
+        let value = tree_value(config, var.get_expr(), tree_name, garden_name);
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    };
+    if let Some(commit) = evaluate(&tree.commit) {
+        return Some(model::TreePin::Commit(commit));
+    }
+    if let Some(tag) = evaluate(&tree.tag) {
+        return Some(model::TreePin::Tag(tag));
+    }
+    evaluate(&tree.git_ref).map(model::TreePin::Ref)
+}
+
+/// Evaluate the gitconfig settings for a tree.
+/// Global, garden and tree gitconfig settings are applied in order of
+/// increasing precedence. Tree settings override garden settings, and
+/// garden settings override global settings.
+pub fn tree_gitconfig(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+) -> Vec<(String, Vec<String>)> {
+    let mut gitconfig = IndexMap::new();
+    let tree = match config.trees.get(&ctx.tree) {
+        Some(tree) => tree,
+        None => return Vec::new(),
+    };
+    let garden_gitconfig = ctx
+        .garden
+        .as_ref()
+        .and_then(|garden| config.gardens.get(garden))
+        .map(|garden| &garden.gitconfig);
+    let scopes = [
+        Some(&config.gitconfig),
+        garden_gitconfig,
+        Some(&tree.gitconfig),
+    ];
+    for (var_name, vars) in scopes.into_iter().flatten().flatten() {
+        let name = tree_value(config, var_name, &ctx.tree, ctx.garden.as_ref());
+        let values = vars
+            .iter()
+            .map(|var| match var.get_value() {
+                Some(precomputed_value) => precomputed_value.to_string(),
+                None => tree_value(config, var.get_expr(), &ctx.tree, ctx.garden.as_ref()),
+            })
+            .collect();
+        gitconfig.insert(name, values);
+    }
+
+    gitconfig.into_iter().collect()
+}
+
 /// Resolve a variable in configuration/global scope
 pub fn value(config: &model::Configuration, expr: &str) -> String {
     let is_exec = syntax::is_exec(expr);
@@ -448,12 +517,25 @@ pub fn environment(
     result
 }
 
-/// Evaluate commands
+/// Evaluate commands. Commands whose "when" predicate is not satisfied are omitted.
 pub fn command(
     app: &model::ApplicationContext,
     context: &model::TreeContext,
     name: &str,
 ) -> Vec<Vec<String>> {
+    commands(app, context, name)
+        .into_iter()
+        .filter(|evaluated| !evaluated.skip)
+        .map(|evaluated| evaluated.commands)
+        .collect()
+}
+
+/// Evaluate commands along with their command settings
+pub fn commands(
+    app: &model::ApplicationContext,
+    context: &model::TreeContext,
+    name: &str,
+) -> Vec<model::EvaluatedCommand> {
     let mut vec_variables = Vec::new();
     let mut result = Vec::new();
     let config = match context.config {
@@ -469,7 +551,8 @@ pub fn command(
     // Global commands
     for (var_name, var) in &config.commands {
         if pattern.matches(var_name) {
-            vec_variables.push(var.clone());
+            let settings = config.command_settings.get(var_name);
+            vec_variables.push((var_name.clone(), var.clone(), settings));
         }
     }
 
@@ -477,7 +560,8 @@ pub fn command(
     if let Some(tree) = config.trees.get(&context.tree) {
         for (var_name, var) in &tree.commands {
             if pattern.matches(var_name) {
-                vec_variables.push(var.clone());
+                let settings = tree.command_settings.get(var_name);
+                vec_variables.push((var_name.clone(), var.clone(), settings));
             }
         }
     }
@@ -487,15 +571,111 @@ pub fn command(
         if let Some(garden) = &config.gardens.get(garden_name) {
             for (var_name, var) in &garden.commands {
                 if pattern.matches(var_name) {
-                    vec_variables.push(var.clone());
+                    let settings = garden.command_settings.get(var_name);
+                    vec_variables.push((var_name.clone(), var.clone(), settings));
                 }
             }
         }
     }
 
-    for variables in vec_variables.iter_mut() {
-        result.push(variables_for_shell(config, variables, context));
+    for (var_name, variables, settings) in vec_variables.iter_mut() {
+        // Commands that are skipped by their "when" predicate are not evaluated.
+        if let Some(settings) = settings {
+            if !command_predicate(config, context, &settings.when) {
+                result.push(model::EvaluatedCommand {
+                    name: var_name.clone(),
+                    skip: true,
+                    ..Default::default()
+                });
+                continue;
+            }
+        }
+        let mut evaluated = model::EvaluatedCommand {
+            name: var_name.clone(),
+            commands: variables_for_shell(config, variables, context),
+            ..Default::default()
+        };
+        if let Some(settings) = settings {
+            command_settings(config, context, settings, &mut evaluated);
+        }
+        result.push(evaluated);
     }
 
     result
 }
+
+/// Evaluate a command's "when" predicate for a tree context.
+/// Returns true when all of the conditions are satisfied.
+pub fn command_predicate(
+    config: &model::Configuration,
+    context: &model::TreeContext,
+    predicate: &model::CommandPredicate,
+) -> bool {
+    if predicate.is_empty() {
+        return true;
+    }
+    let garden_name = context.garden.as_ref();
+    let pathbuf = match config.get_tree_pathbuf(&context.tree) {
+        Some(pathbuf) => pathbuf,
+        None => return false,
+    };
+    let exists = predicate.exists.iter().all(|var| {
+        let value = tree_value(config, var.get_expr(), &context.tree, garden_name);
+        pathbuf.join(value).exists()
+    });
+    if !exists {
+        return false;
+    }
+    let nonempty = predicate
+        .nonempty
+        .iter()
+        .all(|var| !tree_value(config, var.get_expr(), &context.tree, garden_name).is_empty());
+    if !nonempty {
+        return false;
+    }
+
+    predicate.exec.iter().all(|var| {
+        let command = tree_value_for_shell(config, var.get_expr(), &context.tree, garden_name);
+        exec_predicate(&command, &pathbuf)
+    })
+}
+
+/// Run a shell command inside the tree's directory and return true when it succeeds.
+fn exec_predicate(command: &str, pathbuf: &std::path::Path) -> bool {
+    let current_dir = path::current_dir_string();
+    let exec = subprocess::Exec::shell(command)
+        .cwd(pathbuf)
+        .env("PWD", pathbuf.to_str().unwrap_or(&current_dir))
+        .stdout(subprocess::NullFile)
+        .stderr(subprocess::NullFile);
+
+    cmd::status(exec.join()) == errors::EX_OK
+}
+
+/// Evaluate command settings into an EvaluatedCommand
+fn command_settings(
+    config: &model::Configuration,
+    context: &model::TreeContext,
+    settings: &model::CommandSettings,
+    evaluated: &mut model::EvaluatedCommand,
+) {
+    let garden_name = context.garden.as_ref();
+    if !settings.shell.is_empty() {
+        evaluated.shell = Some(settings.shell.clone());
+    }
+    let cwd_expr = settings.cwd.get_expr();
+    if !cwd_expr.is_empty() {
+        evaluated.cwd = Some(tree_value(config, cwd_expr, &context.tree, garden_name));
+    }
+    for var in &settings.environment {
+        let value = tree_value(config, var.get_expr(), &context.tree, garden_name);
+        evaluated.environment.push((var.get_name().clone(), value));
+    }
+    evaluated.confirm = settings.confirm;
+    evaluated.timeout = settings.timeout;
+    evaluated.retries = settings.retries;
+    evaluated.retry_delay = settings.retry_delay;
+    evaluated.depends = settings.depends.clone();
+    evaluated.pre = settings.pre.clone();
+    evaluated.post = settings.post.clone();
+}
@@ -1,5 +1,6 @@
 use super::cmd;
 use super::errors;
+use super::model::GitStatus;
 use super::model::GitTreeDetails;
 use super::model::GitTreeType;
 use super::path;
@@ -63,3 +64,74 @@ pub fn worktree_details(pathbuf: &std::path::Path) -> Result<GitTreeDetails, err
         tree_type: GitTreeType::Worktree(parent_path),
     })
 }
+
+/// Return the commit ID for a revision in the repository at the specified path.
+/// None is returned when the revision does not resolve to a commit.
+pub fn rev_parse_commit(path: &str, revision: &str) -> Option<String> {
+    let rev = format!("{revision}^{{commit}}");
+    let cmd = ["git", "rev-parse", "--verify", "--quiet", rev.as_str()];
+    let exec = cmd::exec_in_dir(&cmd, path);
+    match cmd::capture(exec) {
+        Ok(capture) if capture.success() => Some(cmd::trim_stdout(&capture)),
+        _ => None,
+    }
+}
+
+/// Return the name of the branch that is checked out at the specified path.
+/// None is returned when HEAD is detached.
+pub fn current_branch(path: &str) -> Option<String> {
+    let cmd = ["git", "symbolic-ref", "--quiet", "--short", "HEAD"];
+    let exec = cmd::exec_in_dir(&cmd, path);
+    match cmd::capture(exec) {
+        Ok(capture) if capture.success() => Some(cmd::trim_stdout(&capture)),
+        _ => None,
+    }
+}
+
+/// Return Ok(garden::model::GitStatus) for the worktree at the specified path
+/// or Err(garden::errors::CommandError) when Git commands error out.
+pub fn status(path: &str) -> Result<GitStatus, errors::CommandError> {
+    let cmd = ["git", "status", "--porcelain=v2", "--branch"];
+    let exec = cmd::exec_in_dir(&cmd, path);
+    let capture = cmd::capture(exec)?;
+    if !capture.success() {
+        return Err(errors::CommandError::ExitStatus {
+            command: cmd.join(" "),
+            status: cmd::status(Ok(capture.exit_status)),
+        });
+    }
+
+    let mut status = GitStatus::default();
+    for line in capture.stdout_str().lines() {
+        if let Some(branch) = line.strip_prefix("# branch.head ") {
+            if branch != "(detached)" {
+                status.branch = branch.to_string();
+            }
+        } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+            status.upstream = upstream.to_string();
+        } else if let Some(ahead_behind) = line.strip_prefix("# branch.ab ") {
+            // "# branch.ab +<ahead> -<behind>"
+            for value in ahead_behind.split_whitespace() {
+                if let Some(ahead) = value.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or_default();
+                } else if let Some(behind) = value.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or_default();
+                }
+            }
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        } else if line.starts_with("1 ") || line.starts_with("2 ") || line.starts_with("u ") {
+            status.dirty += 1;
+        }
+    }
+
+    let cmd = ["git", "stash", "list"];
+    let exec = cmd::exec_in_dir(&cmd, path);
+    if let Ok(capture) = cmd::capture(exec) {
+        if capture.success() {
+            status.stashes = capture.stdout_str().lines().count();
+        }
+    }
+
+    Ok(status)
+}
@@ -38,5 +38,8 @@ pub mod path;
 /// Queries, configuration lookups
 pub mod query;
 
+/// Command results and run reports
+pub mod report;
+
 /// Command-line syntax conventions
 pub mod syntax;
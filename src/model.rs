@@ -103,6 +103,12 @@ fn reset_hashmap_variables(vec_variables: &MultiVariableHashMap) {
 /// An unordered mapping of name to Variable.
 pub type VariableHashMap = HashMap<String, Variable>;
 
+/// An unordered mapping of command names to CommandSettings.
+pub type CommandSettingsHashMap = HashMap<String, CommandSettings>;
+
+/// An unordered mapping of branch names to BranchSettings.
+pub type BranchSettingsHashMap = HashMap<String, BranchSettings>;
+
 // Named variables with a single value
 #[derive(Clone, Debug)]
 pub struct NamedVariable {
@@ -186,23 +192,163 @@ impl MultiVariable {
     }
 }
 
+/// BranchSettings configure the upstream branch tracked by a worktree's local branch.
+#[derive(Clone, Debug, Default)]
+pub struct BranchSettings {
+    /// The remote-tracking branch to track, e.g. "origin/main".
+    pub upstream: Variable,
+    /// The ref used to create the branch when the upstream branch does not exist.
+    pub base: Variable,
+}
+
+/// Commands defined using the mapping syntax can specify additional settings.
+/// Commands defined as plain strings or lists of strings use the default settings.
+#[derive(Clone, Debug, Default)]
+pub struct CommandSettings {
+    /// Shell used to run the command. garden.shell is used when empty.
+    pub shell: String,
+    /// Directory to run the command in. Relative paths are relative to the tree.
+    pub cwd: Variable,
+    /// Environment variables that are set when running the command.
+    pub environment: Vec<NamedVariable>,
+    /// Description displayed by "garden list --commands" and shell completions.
+    pub description: String,
+    /// Prompt for confirmation before running the command in each tree.
+    pub confirm: bool,
+    /// Kill the command after the specified number of seconds.
+    pub timeout: Option<u64>,
+    /// Number of times to retry the command when it fails.
+    pub retries: Option<u64>,
+    /// Number of seconds to wait between retries.
+    pub retry_delay: Option<u64>,
+    /// Conditions that must be satisfied for the command to run in a tree.
+    pub when: CommandPredicate,
+    /// Commands that run once per tree before this command.
+    pub depends: IndexSet<String>,
+    /// Commands that run immediately before this command.
+    pub pre: IndexSet<String>,
+    /// Commands that run immediately after this command succeeds.
+    pub post: IndexSet<String>,
+    /// Named parameters that are exposed as command-line options.
+    pub parameters: Vec<CommandParameter>,
+}
+
+impl_display!(CommandSettings);
+
+/// The type of value accepted by a command parameter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParameterType {
+    /// Parameters accept arbitrary strings.
+    #[default]
+    String,
+    /// Boolean parameters are flags that evaluate to "true" or "false".
+    Bool,
+    /// Integer parameters only accept integer values.
+    Integer,
+}
+
+/// CommandParameter declares a named option for a custom command.
+/// Parameter values are made available to commands as variables.
+#[derive(Clone, Debug, Default)]
+pub struct CommandParameter {
+    /// Name of the parameter. Used for both the "--name" option and the variable.
+    pub name: String,
+    /// Help text displayed by "garden <command> --help".
+    pub help: String,
+    /// Default value used when the option is not specified.
+    pub default: Option<String>,
+    /// Type of value accepted by the parameter.
+    pub kind: ParameterType,
+    /// Optional short option.
+    pub short: Option<char>,
+}
+
+impl_display!(CommandParameter);
+
+/// Conditions that decide whether a command runs in a tree.
+/// All of the conditions must be satisfied for the command to run.
+#[derive(Clone, Debug, Default)]
+pub struct CommandPredicate {
+    /// Paths, relative to the tree, that must exist.
+    pub exists: Vec<Variable>,
+    /// Expressions that must evaluate to non-empty values.
+    pub nonempty: Vec<Variable>,
+    /// Shell commands that must succeed when run in the tree.
+    pub exec: Vec<Variable>,
+}
+
+impl_display!(CommandPredicate);
+
+impl CommandPredicate {
+    /// Return true when no conditions have been specified.
+    pub fn is_empty(&self) -> bool {
+        self.exists.is_empty() && self.nonempty.is_empty() && self.exec.is_empty()
+    }
+}
+
+/// A command sequence evaluated for a specific tree context.
+/// Settings expressions have been evaluated into their final values.
+#[derive(Clone, Debug, Default)]
+pub struct EvaluatedCommand {
+    /// Name of the command.
+    pub name: String,
+    /// Command strings ready for execution by a shell.
+    pub commands: Vec<String>,
+    /// Shell used to run the commands. The configured garden.shell is used when None.
+    pub shell: Option<String>,
+    /// Evaluated working directory. The tree's path is used when None.
+    pub cwd: Option<String>,
+    /// Evaluated environment variables for the command.
+    pub environment: Vec<(String, String)>,
+    /// Prompt for confirmation before running the command.
+    pub confirm: bool,
+    /// Timeout in seconds.
+    pub timeout: Option<u64>,
+    /// Number of times to retry the command when it fails.
+    pub retries: Option<u64>,
+    /// Number of seconds to wait between retries.
+    pub retry_delay: Option<u64>,
+    /// True when the command's "when" predicate is not satisfied by the tree.
+    pub skip: bool,
+    /// Commands that run once per tree before this command.
+    pub depends: IndexSet<String>,
+    /// Commands that run immediately before this command.
+    pub pre: IndexSet<String>,
+    /// Commands that run immediately after this command succeeds.
+    pub post: IndexSet<String>,
+}
+
+impl_display!(EvaluatedCommand);
+
 /// Trees represent a single worktree
 #[derive(Clone, Debug, Default)]
 pub struct Tree {
     pub commands: MultiVariableHashMap,
+    pub command_settings: CommandSettingsHashMap,
     pub environment: Vec<MultiVariable>,
-    pub gitconfig: VariableHashMap,
+    pub gitconfig: MultiVariableHashMap,
     pub remotes: VariableHashMap,
     pub symlink: Variable,
     pub templates: IndexSet<String>,
     pub variables: VariableHashMap,
     pub branch: Variable,
+    pub tag: Variable,
+    pub commit: Variable,
+    pub git_ref: Variable,
+    pub upstream: Variable,
+    pub base: Variable,
+    pub branches: BranchSettingsHashMap,
     pub worktree: Variable,
     pub clone_depth: i64,
+    pub clone_filter: Variable,
+    pub sparse_checkout: Vec<Variable>,
     pub is_single_branch: bool,
+    pub is_remotes_exclusive: bool,
+    pub remotes_keep: IndexSet<String>,
     pub is_symlink: bool,
     pub is_bare_repository: bool,
     pub is_worktree: bool,
+    pub submodules: Submodules,
 
     name: String,
     path: Variable,
@@ -278,9 +424,7 @@ impl Tree {
             var.reset();
         }
 
-        for cfg in self.gitconfig.values() {
-            cfg.reset();
-        }
+        reset_hashmap_variables(&self.gitconfig);
 
         for env in &self.environment {
             env.reset();
@@ -292,10 +436,13 @@ impl Tree {
     /// Copy the guts of another tree into the current tree.
     pub fn clone_from_tree(&mut self, tree: &Tree) {
         append_hashmap(&mut self.commands, &tree.commands);
+        append_hashmap(&mut self.command_settings, &tree.command_settings);
         append_hashmap(&mut self.gitconfig, &tree.gitconfig);
         append_hashmap(&mut self.variables, &tree.variables);
         append_hashmap(&mut self.remotes, &tree.remotes);
+        append_hashmap(&mut self.branches, &tree.branches);
         append_indexset(&mut self.templates, &tree.templates);
+        append_indexset(&mut self.remotes_keep, &tree.remotes_keep);
 
         // "environment" follow last-set-wins semantics.
         self.environment.append(&mut tree.environment.clone());
@@ -305,6 +452,14 @@ impl Tree {
             self.clone_depth = tree.clone_depth;
         }
 
+        if !tree.clone_filter.is_empty() {
+            self.clone_filter = tree.clone_filter.clone();
+        }
+
+        if !tree.sparse_checkout.is_empty() {
+            self.sparse_checkout = tree.sparse_checkout.clone();
+        }
+
         if tree.is_bare_repository {
             self.is_bare_repository = tree.is_bare_repository;
         }
@@ -313,9 +468,16 @@ impl Tree {
             self.is_single_branch = tree.is_single_branch;
         }
 
+        if tree.is_remotes_exclusive {
+            self.is_remotes_exclusive = tree.is_remotes_exclusive;
+        }
+
         if tree.is_worktree {
             self.is_worktree = tree.is_worktree;
         }
+        if tree.submodules != Submodules::Disabled {
+            self.submodules = tree.submodules;
+        }
         if tree.is_symlink {
             self.is_symlink = tree.is_symlink;
         }
@@ -324,6 +486,26 @@ impl Tree {
             self.branch = tree.branch.clone();
         }
 
+        if !tree.tag.is_empty() {
+            self.tag = tree.tag.clone();
+        }
+
+        if !tree.commit.is_empty() {
+            self.commit = tree.commit.clone();
+        }
+
+        if !tree.git_ref.is_empty() {
+            self.git_ref = tree.git_ref.clone();
+        }
+
+        if !tree.upstream.is_empty() {
+            self.upstream = tree.upstream.clone();
+        }
+
+        if !tree.base.is_empty() {
+            self.base = tree.base.clone();
+        }
+
         if !tree.symlink.is_empty() {
             self.symlink = tree.symlink.clone();
         }
@@ -403,8 +585,9 @@ impl Template {
 #[derive(Clone, Debug, Default)]
 pub struct Garden {
     pub commands: MultiVariableHashMap,
+    pub command_settings: CommandSettingsHashMap,
     pub environment: Vec<MultiVariable>,
-    pub gitconfig: VariableHashMap,
+    pub gitconfig: MultiVariableHashMap,
     pub groups: IndexSet<String>,
     pub trees: IndexSet<String>,
     pub variables: VariableHashMap,
@@ -438,10 +621,14 @@ fn get_default_shell() -> String {
 // Configuration represents an instantiated garden configuration
 #[derive(Clone, Debug, Default)]
 pub struct Configuration {
+    pub cache: Variable,
+    pub cache_dissociate: bool,
     pub commands: MultiVariableHashMap,
+    pub command_settings: CommandSettingsHashMap,
     pub debug: HashMap<String, u8>,
     pub environment: Vec<MultiVariable>,
     pub gardens: IndexMap<GardenName, Garden>,
+    pub gitconfig: MultiVariableHashMap,
     pub grafts: IndexMap<GraftName, Graft>,
     pub groups: IndexMap<GroupName, Group>,
     pub path: Option<std::path::PathBuf>,
@@ -489,6 +676,20 @@ impl Configuration {
         self.reset();
     }
 
+    /// Return the "garden.cache" directory containing bare mirrors of remote URLs.
+    /// Relative paths are relative to the garden root.
+    pub fn get_cache_path(&self) -> Option<std::path::PathBuf> {
+        if self.cache.is_empty() {
+            return None;
+        }
+        let value = eval::value(self, self.cache.get_expr());
+        if value.is_empty() {
+            return None;
+        }
+
+        Some(self.root_path.join(value))
+    }
+
     pub fn reset(&mut self) {
         // Reset variables to allow for tree-scope evaluation
         self.reset_variables();
@@ -718,6 +919,11 @@ impl Configuration {
         self.path = Some(path);
     }
 
+    /// Return the directory where garden records state for the garden root.
+    pub fn get_state_dir(&self) -> std::path::PathBuf {
+        self.root_path.join(".garden")
+    }
+
     /// Get the config path if it is defined.
     pub fn get_path(&self) -> Result<&std::path::PathBuf, errors::GardenError> {
         self.path
@@ -978,6 +1184,90 @@ impl std::str::FromStr for ColorMode {
     }
 }
 
+/// OutputMode controls how output from commands run in trees is displayed.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Commands write directly to the terminal
+    #[default]
+    Inherit,
+    /// Prefix each line of output with the tree name
+    Prefix,
+    /// Buffer output and display each tree's output as a single block
+    Block,
+}
+
+/// Submodules controls how submodules are initialized when trees are grown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Submodules {
+    /// Submodules are not initialized.
+    #[default]
+    Disabled,
+    /// Submodules are initialized using "git submodule update --init".
+    Enabled,
+    /// Nested submodules are initialized using "git submodule update --init --recursive".
+    Recursive,
+}
+
+/// TreePin is the evaluated "tag", "commit" or "ref" that a tree is pinned to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreePin {
+    Tag(String),
+    Commit(String),
+    Ref(String),
+}
+
+impl TreePin {
+    /// Return the revision used by "git checkout" and "git rev-parse".
+    pub fn revision(&self) -> String {
+        match self {
+            TreePin::Tag(tag) => format!("refs/tags/{tag}"),
+            TreePin::Commit(commit) => commit.clone(),
+            TreePin::Ref(git_ref) => git_ref.clone(),
+        }
+    }
+
+    /// Return the refspec used to fetch the pinned revision when it is missing.
+    pub fn refspec(&self) -> Option<String> {
+        match self {
+            TreePin::Tag(tag) => Some(format!("refs/tags/{tag}:refs/tags/{tag}")),
+            TreePin::Commit(commit) => Some(commit.clone()),
+            TreePin::Ref(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TreePin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreePin::Tag(tag) => write!(f, "tag {tag}"),
+            TreePin::Commit(commit) => write!(f, "commit {commit}"),
+            TreePin::Ref(git_ref) => write!(f, "ref {git_ref}"),
+        }
+    }
+}
+
+/// ReportFormat controls the file format used by "garden cmd --report".
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// JSON document containing a list of results
+    #[default]
+    Json,
+    /// JUnit XML with one test suite per command and one test case per tree
+    Junit,
+}
+
+/// OutputFormat controls the output of "garden inspect", "garden list" and "garden query".
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON document
+    Json,
+    /// YAML document
+    Yaml,
+}
+
 // Color is an alias for yansi::Paint.
 pub type Color<T> = yansi::Paint<T>;
 
@@ -1117,3 +1407,24 @@ pub struct GitTreeDetails {
 }
 
 impl_display!(GitTreeDetails);
+
+/// Represent the "git status" of a worktree queried from Git.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GitStatus {
+    /// The current branch. Empty when HEAD is detached.
+    pub branch: String,
+    /// The upstream branch of the current branch, e.g. "origin/main".
+    pub upstream: String,
+    /// Number of commits on the current branch that are not on the upstream branch.
+    pub ahead: usize,
+    /// Number of commits on the upstream branch that are not on the current branch.
+    pub behind: usize,
+    /// Number of modified, staged and unmerged paths.
+    pub dirty: usize,
+    /// Number of untracked paths.
+    pub untracked: usize,
+    /// Number of stash entries.
+    pub stashes: usize,
+}
+
+impl_display!(GitStatus);
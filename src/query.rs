@@ -5,6 +5,8 @@ use super::path;
 use super::query;
 use super::syntax;
 
+use std::collections::HashSet;
+
 /// Resolve a tree query into a `Vec<garden::model::TreeContext>`.
 ///
 /// Parameters:
@@ -14,6 +16,9 @@ use super::syntax;
 /// - `Vec<garden::model::TreeContext>`
 
 pub fn resolve_trees(config: &model::Configuration, query: &str) -> Vec<model::TreeContext> {
+    if syntax::is_compound_query(query) {
+        return resolve_compound_trees(config, query);
+    }
     let mut result = Vec::new();
     let tree_query = model::TreeQuery::new(query);
     let pattern = &tree_query.pattern;
@@ -61,6 +66,90 @@ pub fn resolve_trees(config: &model::Configuration, query: &str) -> Vec<model::T
     result
 }
 
+/// Set operators used to combine the trees resolved by tree queries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SetOperator {
+    /// "&" keeps the trees that are matched by both queries.
+    Intersection,
+    /// " - " removes the trees that are matched by the second query.
+    Exclusion,
+}
+
+/// Resolve a compound tree query into a `Vec<garden::model::TreeContext>`.
+/// Comma-separated queries are combined using a union. Within each of the
+/// comma-separated queries the "&" and " - " operators are applied from left to right.
+/// Trees are returned in the order in which they are first matched and each tree
+/// is returned at most once per garden context.
+fn resolve_compound_trees(config: &model::Configuration, query: &str) -> Vec<model::TreeContext> {
+    let mut result = Vec::new();
+    let mut contexts = HashSet::new();
+    for term in query.split(',') {
+        for ctx in resolve_term_trees(config, term) {
+            if contexts.insert((ctx.tree.clone(), ctx.garden.clone())) {
+                result.push(ctx);
+            }
+        }
+    }
+
+    result
+}
+
+/// Resolve a query containing "&" and " - " set operators.
+/// The trees on the left are kept or removed according to whether the tree
+/// is matched by the query on the right, regardless of its garden context.
+fn resolve_term_trees(config: &model::Configuration, term: &str) -> Vec<model::TreeContext> {
+    let (operands, operators) = split_set_operators(term);
+    let mut result = resolve_operand_trees(config, operands[0]);
+    for (operator, operand) in operators.iter().zip(&operands[1..]) {
+        let operand_names: HashSet<String> = resolve_operand_trees(config, operand)
+            .into_iter()
+            .map(|ctx| ctx.tree)
+            .collect();
+        let is_intersection = *operator == SetOperator::Intersection;
+        result.retain(|ctx| operand_names.contains(&ctx.tree) == is_intersection);
+    }
+
+    result
+}
+
+/// Resolve a single operand from a compound query. Empty operands match no trees.
+fn resolve_operand_trees(config: &model::Configuration, operand: &str) -> Vec<model::TreeContext> {
+    let operand = operand.trim();
+    if operand.is_empty() {
+        return Vec::new();
+    }
+
+    resolve_trees(config, operand)
+}
+
+/// Split a query into its operands and the set operators between them.
+/// "-" is only an operator when it is surrounded by whitespace.
+fn split_set_operators(term: &str) -> (Vec<&str>, Vec<SetOperator>) {
+    let mut operands = Vec::new();
+    let mut operators = Vec::new();
+    let bytes = term.as_bytes();
+    let is_space = |index: Option<usize>| match index.and_then(|index| bytes.get(index)) {
+        Some(byte) => byte.is_ascii_whitespace(),
+        None => true,
+    };
+    let mut start = 0;
+    for (index, byte) in bytes.iter().enumerate() {
+        let operator = match byte {
+            b'&' => SetOperator::Intersection,
+            b'-' if is_space(index.checked_sub(1)) && is_space(Some(index + 1)) => {
+                SetOperator::Exclusion
+            }
+            _ => continue,
+        };
+        operands.push(&term[start..index]);
+        operators.push(operator);
+        start = index + 1;
+    }
+    operands.push(&term[start..]);
+
+    (operands, operators)
+}
+
 /// Return tree contexts for every garden matching the specified pattern.
 /// Parameters:
 /// - config: `&garden::model::Configuration`
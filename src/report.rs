@@ -0,0 +1,417 @@
+use super::errors;
+use super::model;
+
+use indexmap::IndexMap;
+use yaml_rust::yaml::Hash as YamlHash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// CommandResult records the outcome of running a command in a tree.
+#[derive(Clone, Debug, Default)]
+pub struct CommandResult {
+    /// Tree query that selected the tree.
+    pub query: String,
+    /// Name of the tree that the command ran in.
+    pub tree: String,
+    /// Optional garden context for the tree.
+    pub garden: Option<String>,
+    /// Name of the command.
+    pub command: String,
+    /// Exit status returned by the command, or EX_SKIPPED when the command
+    /// did not run because a command that it requires has failed.
+    pub status: i32,
+    /// Wall-clock time spent running the command.
+    pub duration: std::time::Duration,
+}
+
+impl CommandResult {
+    /// Return true when the command succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.status == errors::EX_OK
+    }
+
+    /// Return true when the command was skipped because a required command failed.
+    pub fn is_skipped(&self) -> bool {
+        self.status == errors::EX_SKIPPED
+    }
+
+    /// Describe the status for display.
+    fn display_status(&self) -> String {
+        if self.is_ok() {
+            string!("ok")
+        } else if self.is_skipped() {
+            string!("skipped")
+        } else {
+            format!("exit {}", self.status)
+        }
+    }
+}
+
+/// Print a table summarizing the results to stderr.
+pub fn print_summary(results: &[CommandResult]) {
+    if results.is_empty() {
+        return;
+    }
+    let rows: Vec<(&str, &str, String, String)> = results
+        .iter()
+        .map(|result| {
+            let status = result.display_status();
+            let duration = format!("{:.2}s", result.duration.as_secs_f64());
+            (
+                result.tree.as_str(),
+                result.command.as_str(),
+                status,
+                duration,
+            )
+        })
+        .collect();
+    let tree_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let command_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+    let status_width = rows.iter().map(|row| row.2.len()).max().unwrap_or(0);
+
+    eprintln!(
+        "{} {}",
+        model::Color::cyan("#"),
+        model::Color::blue("summary").bold()
+    );
+    for (result, (tree, command, status, duration)) in results.iter().zip(&rows) {
+        // Padding is applied before colors so that escape sequences do not affect alignment.
+        let status = format!("{status:<status_width$}");
+        let status = if result.is_ok() {
+            model::Color::green(status)
+        } else {
+            model::Color::red(status).bold()
+        };
+        eprintln!(
+            "{}  {command:<command_width$}  {status}  {duration}",
+            model::Color::blue(format!("{tree:<tree_width$}")).bold(),
+        );
+    }
+}
+
+/// Write the results to a file using the specified format.
+pub fn write_report(
+    path: &std::path::Path,
+    format: model::ReportFormat,
+    results: &[CommandResult],
+) -> Result<(), errors::GardenError> {
+    let contents = match format {
+        model::ReportFormat::Json => to_json(results),
+        model::ReportFormat::Junit => to_junit(results),
+    };
+    std::fs::write(path, contents).map_err(|err| errors::GardenError::WriteFile {
+        path: path.to_path_buf(),
+        err,
+    })
+}
+
+/// Read results from a JSON report. Missing reports contain no results.
+/// Reports that cannot be read or parsed are reported as errors.
+pub fn read_results(path: &std::path::Path) -> Result<Vec<CommandResult>, errors::GardenError> {
+    let mut results = Vec::new();
+    if !path.exists() {
+        return Ok(results);
+    }
+    let string = std::fs::read_to_string(path).map_err(|err| errors::GardenError::ReadFile {
+        path: path.to_path_buf(),
+        err,
+    })?;
+    // JSON documents are also valid YAML documents.
+    let docs =
+        YamlLoader::load_from_str(&string).map_err(|err| errors::GardenError::ReadConfig {
+            err,
+            path: path.display().to_string(),
+        })?;
+    let invalid = || {
+        errors::GardenError::ConfigurationError(format!("invalid results in {}", path.display()))
+    };
+    let entries = match docs.first().map(|doc| &doc["results"]) {
+        Some(Yaml::Array(entries)) => entries,
+        _ => return Err(invalid()),
+    };
+    for entry in entries {
+        let (Some(query), Some(tree), Some(command), Some(status)) = (
+            entry["query"].as_str(),
+            entry["tree"].as_str(),
+            entry["command"].as_str(),
+            entry["status"].as_i64(),
+        ) else {
+            return Err(invalid());
+        };
+        let duration = match &entry["duration"] {
+            Yaml::Real(_) => entry["duration"].as_f64().unwrap_or_default(),
+            Yaml::Integer(value) => *value as f64,
+            _ => 0.0,
+        };
+        results.push(CommandResult {
+            query: query.to_string(),
+            tree: tree.to_string(),
+            garden: entry["garden"].as_str().map(|garden| garden.to_string()),
+            command: command.to_string(),
+            status: status as i32,
+            duration: std::time::Duration::from_secs_f64(duration.max(0.0)),
+        });
+    }
+
+    Ok(results)
+}
+
+/// Format results as a JSON document.
+pub fn to_json(results: &[CommandResult]) -> String {
+    let mut json = string!("{\n  \"results\": [");
+    for (idx, result) in results.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        let garden = match &result.garden {
+            Some(garden) => json_string(garden),
+            None => string!("null"),
+        };
+        json.push_str(&format!(
+            concat!(
+                "\n    {{",
+                "\"query\": {}, ",
+                "\"tree\": {}, ",
+                "\"garden\": {}, ",
+                "\"command\": {}, ",
+                "\"status\": {}, ",
+                "\"duration\": {:.3}",
+                "}}"
+            ),
+            json_string(&result.query),
+            json_string(&result.tree),
+            garden,
+            json_string(&result.command),
+            result.status,
+            result.duration.as_secs_f64(),
+        ));
+    }
+    if !results.is_empty() {
+        json.push_str("\n  ");
+    }
+    json.push_str("]\n}\n");
+
+    json
+}
+
+/// Format results as a JUnit XML document. Each command is reported as a
+/// test suite containing one test case per tree. Commands that did not run
+/// because a required command failed are reported as skipped test cases.
+pub fn to_junit(results: &[CommandResult]) -> String {
+    let mut suites: IndexMap<&str, Vec<&CommandResult>> = IndexMap::new();
+    for result in results {
+        suites
+            .entry(result.command.as_str())
+            .or_default()
+            .push(result);
+    }
+    let (failures, skipped) = junit_counts(results.iter());
+    let time: f64 = results
+        .iter()
+        .map(|result| result.duration.as_secs_f64())
+        .sum();
+
+    let mut xml = string!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"garden\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        results.len(),
+        failures,
+        skipped,
+        time
+    ));
+    for (command, suite) in &suites {
+        let (suite_failures, suite_skipped) = junit_counts(suite.iter().copied());
+        let suite_time: f64 = suite
+            .iter()
+            .map(|result| result.duration.as_secs_f64())
+            .sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(command),
+            suite.len(),
+            suite_failures,
+            suite_skipped,
+            suite_time
+        ));
+        for result in suite {
+            let testcase = format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                xml_escape(command),
+                xml_escape(&result.tree),
+                result.duration.as_secs_f64()
+            );
+            if result.is_ok() {
+                xml.push_str(&format!("{testcase}/>\n"));
+            } else if result.is_skipped() {
+                xml.push_str(&format!(
+                    "{testcase}>\n      <skipped message=\"a required command failed\"/>\n    </testcase>\n"
+                ));
+            } else {
+                xml.push_str(&format!(
+                    "{testcase}>\n      <failure message=\"exit status {}\"/>\n    </testcase>\n",
+                    result.status
+                ));
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+/// Count the failed and skipped results for a JUnit test suite.
+fn junit_counts<'a>(results: impl Iterator<Item = &'a CommandResult>) -> (usize, usize) {
+    let mut failures = 0;
+    let mut skipped = 0;
+    for result in results {
+        if result.is_skipped() {
+            skipped += 1;
+        } else if !result.is_ok() {
+            failures += 1;
+        }
+    }
+
+    (failures, skipped)
+}
+
+/// Format a structured document as JSON or YAML.
+pub fn format_document(doc: &Yaml, format: model::OutputFormat) -> String {
+    match format {
+        model::OutputFormat::Yaml => {
+            let mut yaml = String::new();
+            let mut emitter = YamlEmitter::new(&mut yaml);
+            emitter.multiline_strings(true);
+            emitter.dump(doc).unwrap_or(());
+            yaml.push('\n');
+            yaml
+        }
+        _ => {
+            let mut json = String::new();
+            yaml_to_json(doc, 0, &mut json);
+            json.push('\n');
+            json
+        }
+    }
+}
+
+/// Return a structured document for a resolved tree context.
+/// The document contains the tree, garden, group, configuration id and tree path.
+pub fn context_document(
+    config: &model::Configuration,
+    ctx: &model::TreeContext,
+) -> Result<YamlHash, errors::GardenError> {
+    let mut doc = YamlHash::new();
+    doc.insert(yaml_str("tree"), yaml_str(&ctx.tree));
+    doc.insert(yaml_str("garden"), yaml_option(ctx.garden.as_deref()));
+    doc.insert(yaml_str("group"), yaml_option(ctx.group.as_deref()));
+    let config_id = match ctx.config {
+        Some(id) => {
+            let id: usize = id.into();
+            Yaml::Integer(id as i64)
+        }
+        None => Yaml::Null,
+    };
+    doc.insert(yaml_str("config"), config_id);
+    let path = match config.trees.get(&ctx.tree) {
+        Some(tree) => yaml_str(tree.path_as_ref()?),
+        None => Yaml::Null,
+    };
+    doc.insert(yaml_str("path"), path);
+
+    Ok(doc)
+}
+
+/// Return a Yaml string.
+pub fn yaml_str(value: &str) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+/// Return a Yaml string or null.
+pub fn yaml_option(value: Option<&str>) -> Yaml {
+    match value {
+        Some(value) => yaml_str(value),
+        None => Yaml::Null,
+    }
+}
+
+/// Format a Yaml value as an indented JSON value.
+fn yaml_to_json(value: &Yaml, depth: usize, json: &mut String) {
+    let indent = "  ".repeat(depth + 1);
+    let closing_indent = "  ".repeat(depth);
+    match value {
+        Yaml::Hash(hash) if hash.is_empty() => json.push_str("{}"),
+        Yaml::Hash(hash) => {
+            json.push('{');
+            for (idx, (key, value)) in hash.iter().enumerate() {
+                if idx > 0 {
+                    json.push(',');
+                }
+                let key = match key {
+                    Yaml::String(key) => key.clone(),
+                    Yaml::Integer(key) => key.to_string(),
+                    _ => continue,
+                };
+                json.push_str(&format!("\n{indent}{}: ", json_string(&key)));
+                yaml_to_json(value, depth + 1, json);
+            }
+            json.push_str(&format!("\n{closing_indent}}}"));
+        }
+        Yaml::Array(array) if array.is_empty() => json.push_str("[]"),
+        Yaml::Array(array) => {
+            json.push('[');
+            for (idx, value) in array.iter().enumerate() {
+                if idx > 0 {
+                    json.push(',');
+                }
+                json.push_str(&format!("\n{indent}"));
+                yaml_to_json(value, depth + 1, json);
+            }
+            json.push_str(&format!("\n{closing_indent}]"));
+        }
+        Yaml::String(value) => json.push_str(&json_string(value)),
+        Yaml::Integer(value) => json.push_str(&value.to_string()),
+        Yaml::Real(real) => json.push_str(&json_real(real)),
+        Yaml::Boolean(value) => json.push_str(&value.to_string()),
+        _ => json.push_str("null"),
+    }
+}
+
+/// Format a YAML real number as a JSON value. YAML reals such as "1e3" and ".5"
+/// are not valid JSON numbers so they are normalized. Infinities and NaN cannot be
+/// represented in JSON and are written as strings.
+fn json_real(real: &str) -> String {
+    match Yaml::Real(real.to_string()).as_f64() {
+        Some(value) if value.is_finite() => format!("{value:?}"),
+        _ => json_string(real),
+    }
+}
+
+/// Quote and escape a string for use in a JSON document.
+pub fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+/// Escape a string for use in XML attribute values.
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -23,6 +23,14 @@ pub fn is_tree(string: &str) -> bool {
     string.starts_with('@')
 }
 
+/// Return true if `string` is a compound tree query that combines queries using
+/// the "," union, "&" intersection or " - " exclusion operators.
+/// The exclusion operator must be surrounded by whitespace so that it is not
+/// confused with names that contain dashes.
+pub fn is_compound_query(string: &str) -> bool {
+    string.contains([',', '&']) || string.split_whitespace().any(|word| word == "-")
+}
+
 /// Return true if `string` is a `graft::value` expression.
 pub fn is_graft(string: &str) -> bool {
     string.contains("::")
@@ -165,6 +165,24 @@ pub fn garden_capture(args: &[&str]) -> String {
     utf8_result.unwrap().trim_end().into()
 }
 
+/// Execute the "garden" command with the specified arguments and return its stderr.
+pub fn garden_capture_stderr(args: &[&str]) -> String {
+    let mut exec = Command::cargo_bin("garden").expect("garden not found");
+    exec.args(args);
+
+    let capture = exec.output().expect("unable to run garden");
+    String::from_utf8_lossy(&capture.stderr).trim_end().into()
+}
+
+/// Execute the "garden" command with the specified arguments and return its exit status.
+pub fn garden_exit_status(args: &[&str]) -> i32 {
+    let mut exec = Command::cargo_bin("garden").expect("garden not found");
+    exec.args(args);
+
+    let capture = exec.output().expect("unable to run garden");
+    capture.status.code().unwrap_or(errors::EX_ERROR)
+}
+
 /// Execute a command and ensure that the exit status is returned.
 pub fn assert_cmd_status(cmd: &[&str], directory: &str, status: i32) {
     let exec = cmd::exec_in_dir(cmd, directory);
@@ -294,3 +312,52 @@ impl Drop for BareRepoFixture<'_> {
         teardown_tmp_test_data(&self.root());
     }
 }
+
+/// Provide an empty garden root directory for the current test.
+pub struct TmpRootFixture<'a> {
+    name: &'a str,
+}
+
+impl<'a> TmpRootFixture<'a> {
+    /// Create an empty temporary directory, removing stale data from earlier runs.
+    pub fn new(name: &'a str) -> Self {
+        let fixture = Self { name };
+        std::fs::remove_dir_all(fixture.root()).unwrap_or(());
+        if let Err(err) = std::fs::create_dir_all(fixture.root()) {
+            panic!("unable to create '{}': {err}", fixture.root());
+        }
+
+        fixture
+    }
+
+    /// Return the temporary directory for the current test.
+    pub fn root(&self) -> String {
+        format!("tests/tmp/{}", self.name)
+    }
+
+    /// Return the absolute path to the temporary directory.
+    /// Absolute paths are needed when passing "--root" alongside "--chdir".
+    pub fn root_abs(&self) -> String {
+        self.pathbuf("").to_string_lossy().to_string()
+    }
+
+    /// Return an absolute PathBuf relative to the temporary directory.
+    /// Unlike BareRepoFixture::pathbuf() the path does not need to exist.
+    pub fn pathbuf(&self, path: &str) -> std::path::PathBuf {
+        let root = std::env::current_dir()
+            .unwrap_or_default()
+            .join(self.root());
+        if path.is_empty() {
+            root
+        } else {
+            root.join(path)
+        }
+    }
+}
+
+impl Drop for TmpRootFixture<'_> {
+    /// Remove the temporary directory.
+    fn drop(&mut self) {
+        teardown_tmp_test_data(&self.root());
+    }
+}
@@ -106,6 +106,169 @@ fn commands() -> Result<()> {
     Ok(())
 }
 
+/// Commands defined using the mapping syntax
+#[test]
+fn commands_structured() -> Result<()> {
+    let string = string!(
+        r#"
+    commands:
+        plain: echo plain
+        deploy:
+            description: Deploy the tree
+            shell: bash
+            cwd: ${TREE_NAME}/build
+            confirm: true
+            env:
+                DEPLOY_ENV: production
+                DEPLOY_JOBS: 4
+            run:
+                - make
+                - make deploy
+    "#
+    );
+    let config = common::from_string(&string);
+    assert_eq!(2, config.commands.len());
+
+    let deploy = config.commands.get("deploy").context("deploy")?;
+    assert_eq!(2, deploy.len());
+    assert_eq!("make", deploy[0].get_expr());
+    assert_eq!("make deploy", deploy[1].get_expr());
+
+    let settings = config.command_settings.get("deploy").context("settings")?;
+    assert_eq!("Deploy the tree", settings.description);
+    assert_eq!("bash", settings.shell);
+    assert_eq!("${TREE_NAME}/build", settings.cwd.get_expr());
+    assert!(settings.confirm);
+    assert_eq!(2, settings.environment.len());
+    assert_eq!("DEPLOY_ENV", settings.environment[0].get_name());
+    assert_eq!("production", settings.environment[0].get_expr());
+    assert_eq!("DEPLOY_JOBS", settings.environment[1].get_name());
+    assert_eq!("4", settings.environment[1].get_expr());
+
+    assert!(settings.when.is_empty());
+
+    // Plain commands use the default settings.
+    let settings = config.command_settings.get("plain").context("plain")?;
+    assert!(settings.description.is_empty());
+    assert!(settings.shell.is_empty());
+    assert!(!settings.confirm);
+
+    Ok(())
+}
+
+/// Commands with "when" predicates
+#[test]
+fn commands_when() -> Result<()> {
+    let string = string!(
+        r#"
+    commands:
+        test:
+            when:
+                exists: [Cargo.toml, src]
+                nonempty: ${rust}
+                exec: type cargo
+            run: cargo test
+    "#
+    );
+    let config = common::from_string(&string);
+    let settings = config.command_settings.get("test").context("test")?;
+    assert!(!settings.when.is_empty());
+    assert_eq!(2, settings.when.exists.len());
+    assert_eq!("Cargo.toml", settings.when.exists[0].get_expr());
+    assert_eq!("src", settings.when.exists[1].get_expr());
+    assert_eq!(1, settings.when.nonempty.len());
+    assert_eq!("${rust}", settings.when.nonempty[0].get_expr());
+    assert_eq!(1, settings.when.exec.len());
+    assert_eq!("type cargo", settings.when.exec[0].get_expr());
+
+    Ok(())
+}
+
+/// Commands with declared parameters
+#[test]
+fn commands_params() -> Result<()> {
+    let string = string!(
+        r#"
+    commands:
+        deploy:
+            params:
+                target:
+                    help: Deployment target
+                    default: staging
+                    short: t
+                count:
+                    type: int
+                force: false
+                region: us-east
+            run: deploy ${target}
+    "#
+    );
+    let config = common::from_string(&string);
+    let settings = config.command_settings.get("deploy").context("deploy")?;
+    assert_eq!(4, settings.parameters.len());
+
+    let target = &settings.parameters[0];
+    assert_eq!("target", target.name);
+    assert_eq!("Deployment target", target.help);
+    assert_eq!(Some(string!("staging")), target.default);
+    assert_eq!(garden::model::ParameterType::String, target.kind);
+    assert_eq!(Some('t'), target.short);
+
+    let count = &settings.parameters[1];
+    assert_eq!("count", count.name);
+    assert_eq!(garden::model::ParameterType::Integer, count.kind);
+    assert_eq!(None, count.default);
+
+    let force = &settings.parameters[2];
+    assert_eq!(garden::model::ParameterType::Bool, force.kind);
+    assert_eq!(Some(string!("false")), force.default);
+
+    let region = &settings.parameters[3];
+    assert_eq!(garden::model::ParameterType::String, region.kind);
+    assert_eq!(Some(string!("us-east")), region.default);
+
+    Ok(())
+}
+
+/// garden.cache specifies a directory containing bare mirrors
+#[test]
+fn cache() {
+    let string = string!(
+        r#"
+    garden:
+        root: /tmp/garden
+        cache: ${cache_dir}
+        cache-dissociate: true
+    variables:
+        cache_dir: cache
+    "#
+    );
+    let config = common::from_string(&string);
+    assert!(config.cache_dissociate);
+    let cache = config.get_cache_path();
+    assert_eq!(Some(std::path::PathBuf::from("/tmp/garden/cache")), cache);
+
+    let cache = std::path::PathBuf::from("/cache");
+    let mirror_path = |url: &str| {
+        garden::cmds::cache::mirror_path(&cache, url)
+            .to_string_lossy()
+            .to_string()
+    };
+    assert_eq!(
+        "/cache/github.com/garden-rs/garden.git",
+        mirror_path("https://github.com/garden-rs/garden")
+    );
+    assert_eq!(
+        "/cache/github.com/garden-rs/garden.git",
+        mirror_path("git@github.com:garden-rs/garden.git")
+    );
+    assert_eq!(
+        "/cache/example.com/repos/example.git",
+        mirror_path("ssh://user@example.com/../repos/example.git/")
+    );
+    assert_eq!("/cache/tmp/repo.git", mirror_path("file:///tmp/repo"));
+}
+
 /// Templates
 #[test]
 fn templates() -> Result<()> {
@@ -253,10 +416,10 @@ fn trees() -> Result<()> {
 
     // gitconfig
     assert_eq!(2, tree0.gitconfig.len());
-    let user_name_var = tree0.gitconfig.get("user.name").context("user.name")?;
+    let user_name_var = &tree0.gitconfig.get("user.name").context("user.name")?[0];
     assert_eq!("A U Thor", user_name_var.get_expr());
     assert_eq!(None, user_name_var.get_value());
-    let user_email_var = tree0.gitconfig.get("user.email").context("user.email")?;
+    let user_email_var = &tree0.gitconfig.get("user.email").context("user.email")?[0];
     assert_eq!("author@example.com", user_email_var.get_expr());
     assert_eq!(None, user_email_var.get_value());
 
@@ -321,7 +484,8 @@ fn trees() -> Result<()> {
         .gitconfig
         .get("remote.origin.annex-ignore")
         .context("annex-ignore")?;
-    assert_eq!("true", annex_ignore_var.get_expr());
+    assert_eq!(1, annex_ignore_var.len());
+    assert_eq!("true", annex_ignore_var[0].get_expr());
     // remotes
     assert_eq!(2, tree3.remotes.len());
     let origin_var = tree3.remotes.get("origin").context("origin")?;
@@ -338,7 +502,8 @@ fn trees() -> Result<()> {
         .gitconfig
         .get("remote.origin.annex-ignore")
         .context("annex-ignore")?;
-    assert_eq!("true", annex_ignore_var.get_expr());
+    assert_eq!(1, annex_ignore_var.len());
+    assert_eq!("true", annex_ignore_var[0].get_expr());
     // remotes
     assert_eq!(2, tree4.remotes.len());
     let origin_var = tree4.remotes.get("origin").context("origin")?;
@@ -451,13 +616,225 @@ fn test_gardens(config: &garden::model::Configuration) -> Result<()> {
         .gitconfig
         .get("user.name")
         .context("user.name")?;
-    assert_eq!("A U Thor", user_name_var.get_expr());
+    assert_eq!("A U Thor", user_name_var[0].get_expr());
 
     let user_email_var = config.gardens[1]
         .gitconfig
         .get("user.email")
         .context("user.email")?;
-    assert_eq!("author@example.com", user_email_var.get_expr());
+    assert_eq!("author@example.com", user_email_var[0].get_expr());
+
+    Ok(())
+}
+
+/// Trees can initialize submodules
+#[test]
+fn tree_submodules() -> Result<()> {
+    let string = string!(
+        r#"
+    trees:
+        plain: https://example.com/plain.git
+        vendored:
+            url: https://example.com/vendored.git
+            submodules: true
+        nested:
+            url: https://example.com/nested.git
+            submodules: recursive
+        nested/dev:
+            worktree: nested
+            branch: dev
+    "#
+    );
+    let config = common::from_string(&string);
+    let tree = config.get_tree("plain").context("plain")?;
+    assert_eq!(garden::model::Submodules::Disabled, tree.submodules);
+    let tree = config.get_tree("vendored").context("vendored")?;
+    assert_eq!(garden::model::Submodules::Enabled, tree.submodules);
+    let tree = config.get_tree("nested").context("nested")?;
+    assert_eq!(garden::model::Submodules::Recursive, tree.submodules);
+    // Worktrees use the parent's settings.
+    let tree = config.get_tree("nested/dev").context("nested/dev")?;
+    assert_eq!(garden::model::Submodules::Recursive, tree.submodules);
+
+    Ok(())
+}
+
+/// Trees can specify partial clone filters and sparse checkouts
+#[test]
+fn tree_sparse_checkout() -> Result<()> {
+    let string = string!(
+        r#"
+    templates:
+        monorepo:
+            filter: blob:none
+            sparse: [build]
+    trees:
+        monorepo:
+            url: https://example.com/monorepo.git
+            templates: monorepo
+            sparse:
+                - docs
+                - ${component}
+    "#
+    );
+    let config = common::from_string(&string);
+    let tree = config.get_tree("monorepo").context("monorepo")?;
+    assert_eq!("blob:none", tree.clone_filter.get_expr());
+    // Sparse paths replace the paths from templates.
+    assert_eq!(2, tree.sparse_checkout.len());
+    assert_eq!("docs", tree.sparse_checkout[0].get_expr());
+    assert_eq!("${component}", tree.sparse_checkout[1].get_expr());
+
+    Ok(())
+}
+
+/// gitconfig values can be lists of values and null values unset settings.
+#[test]
+fn tree_gitconfig_multi_valued() -> Result<()> {
+    let string = string!(
+        r#"
+    trees:
+        example:
+            gitconfig:
+                remote.origin.fetch:
+                    - +refs/heads/*:refs/remotes/origin/*
+                    - +refs/notes/*:refs/notes/*
+                core.bare: false
+                user.signingkey: ~
+                url.${mirror}.insteadOf: []
+    "#
+    );
+    let config = common::from_string(&string);
+    let tree = config.get_tree("example").context("example")?;
+    assert_eq!(4, tree.gitconfig.len());
+    let fetch = tree.gitconfig.get("remote.origin.fetch").context("fetch")?;
+    assert_eq!(2, fetch.len());
+    assert_eq!("+refs/heads/*:refs/remotes/origin/*", fetch[0].get_expr());
+    assert_eq!("+refs/notes/*:refs/notes/*", fetch[1].get_expr());
+    let bare = tree.gitconfig.get("core.bare").context("core.bare")?;
+    assert_eq!("false", bare[0].get_expr());
+    // Null values and empty lists unset settings.
+    let signingkey = tree
+        .gitconfig
+        .get("user.signingkey")
+        .context("signingkey")?;
+    assert!(signingkey.is_empty());
+    let instead_of = tree
+        .gitconfig
+        .get("url.${mirror}.insteadOf")
+        .context("url")?;
+    assert!(instead_of.is_empty());
+
+    Ok(())
+}
+
+/// Trees can remove unconfigured remotes using "remotes-exclusive".
+#[test]
+fn tree_remotes_exclusive() -> Result<()> {
+    let string = string!(
+        r#"
+    templates:
+        exclusive:
+            remotes-exclusive: true
+            remotes-keep: upstream
+    trees:
+        example:
+            templates: exclusive
+            remotes-keep: [fork-*]
+        default: {}
+    "#
+    );
+    let config = common::from_string(&string);
+    let tree = config.get_tree("example").context("example")?;
+    assert!(tree.is_remotes_exclusive);
+    assert_eq!(
+        indexset! {string!("upstream"), string!("fork-*")},
+        tree.remotes_keep
+    );
+    let tree = config.get_tree("default").context("default")?;
+    assert!(!tree.is_remotes_exclusive);
+    assert!(tree.remotes_keep.is_empty());
+
+    Ok(())
+}
+
+/// Trees can configure the upstream branches tracked by worktrees.
+#[test]
+fn tree_branches() -> Result<()> {
+    let string = string!(
+        r#"
+    trees:
+        feature:
+            worktree: main
+            branch: feature
+            upstream: upstream/feature
+            base: origin/main
+            branches:
+                dev: origin/develop
+                topic:
+                    upstream: fork/topic
+                    base: origin/dev
+    "#
+    );
+    let config = common::from_string(&string);
+    let tree = config.get_tree("feature").context("feature")?;
+    assert_eq!("upstream/feature", tree.upstream.get_expr());
+    assert_eq!("origin/main", tree.base.get_expr());
+    assert_eq!(2, tree.branches.len());
+    let dev = tree.branches.get("dev").context("dev")?;
+    assert_eq!("origin/develop", dev.upstream.get_expr());
+    assert!(dev.base.is_empty());
+    let topic = tree.branches.get("topic").context("topic")?;
+    assert_eq!("fork/topic", topic.upstream.get_expr());
+    assert_eq!("origin/dev", topic.base.get_expr());
+
+    Ok(())
+}
+
+/// Trees can be pinned to a tag, commit or ref.
+#[test]
+fn tree_pin() -> Result<()> {
+    let string = string!(
+        r#"
+    variables:
+        version: v1.2.3
+    templates:
+        release:
+            tag: ${version}
+    trees:
+        tagged:
+            templates: release
+        committed:
+            templates: release
+            commit: 0123456789abcdef
+        referenced:
+            ref: origin/stable
+        unpinned:
+            branch: main
+    "#
+    );
+    let config = common::from_string(&string);
+    let pin = |tree: &str| garden::eval::tree_pin(&config, tree, None);
+    assert_eq!(
+        Some(garden::model::TreePin::Tag(string!("v1.2.3"))),
+        pin("tagged")
+    );
+    // Commits take precedence over tags.
+    assert_eq!(
+        Some(garden::model::TreePin::Commit(string!("0123456789abcdef"))),
+        pin("committed")
+    );
+    assert_eq!(
+        Some(garden::model::TreePin::Ref(string!("origin/stable"))),
+        pin("referenced")
+    );
+    assert_eq!(None, pin("unpinned"));
+    let tag = pin("tagged").context("tagged")?;
+    assert_eq!("refs/tags/v1.2.3", tag.revision());
+    assert_eq!(
+        Some(string!("refs/tags/v1.2.3:refs/tags/v1.2.3")),
+        tag.refspec()
+    );
 
     Ok(())
 }
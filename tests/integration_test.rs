@@ -4,14 +4,16 @@
 #[path = "common/mod.rs"]
 pub mod common;
 use common::{
-    assert_cmd, assert_cmd_capture, assert_ref, assert_ref_missing, exec_garden, garden_capture,
-    BareRepoFixture,
+    assert_cmd, assert_cmd_capture, assert_cmd_status, assert_ref, assert_ref_missing, exec_garden,
+    garden_capture, garden_capture_stderr, garden_exit_status, BareRepoFixture, TmpRootFixture,
 };
 
+use garden::errors;
 use garden::git;
 use garden::model;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use assert_cmd::prelude::CommandCargoExt;
 use function_name::named;
 
 /// `garden grow` clones repositories
@@ -341,6 +343,155 @@ fn grow_gitconfig() -> Result<()> {
     Ok(())
 }
 
+/// `garden grow` applies global, garden and tree gitconfig settings
+/// in order of increasing precedence.
+#[test]
+#[named]
+fn grow_gitconfig_scopes() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    exec_garden(&[
+        "--chdir",
+        &fixture.root(),
+        "--config",
+        "tests/data/gitconfig.yaml",
+        "grow",
+        ":work",
+        ":oss",
+    ])?;
+    let git_config = |tree: &str, name: &str| {
+        let cmd = ["git", "config", name];
+        assert_cmd_capture(&cmd, &fixture.worktree(tree))
+    };
+
+    // Global settings apply to all trees.
+    assert_eq!("A U Thor", git_config("work-repo", "user.name"));
+    assert_eq!("A U Thor", git_config("oss-repo", "user.name"));
+    // Garden settings override global settings.
+    assert_eq!(
+        "author@work.example.com",
+        git_config("work-repo", "user.email")
+    );
+    assert_eq!(
+        "author@oss.example.com",
+        git_config("oss-repo", "user.email")
+    );
+    assert_eq!("hooks", git_config("work-repo", "core.hooksPath"));
+    // Tree settings override garden settings.
+    assert_eq!("oss-hooks", git_config("oss-repo", "core.hooksPath"));
+
+    Ok(())
+}
+
+/// `garden grow` applies the gitconfig settings from every garden that a tree is
+/// grown through. Gardens that are specified later take precedence.
+#[test]
+#[named]
+fn grow_gitconfig_multiple_gardens() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let grow = |queries: &[&str]| {
+        let cmd = [
+            &[
+                "--chdir",
+                &root,
+                "--config",
+                "tests/data/gitconfig.yaml",
+                "grow",
+            ],
+            queries,
+        ]
+        .concat();
+        exec_garden(&cmd)
+    };
+    let git_config = |name: &str| {
+        let cmd = ["git", "config", name];
+        assert_cmd_capture(&cmd, &fixture.worktree("work-repo"))
+    };
+
+    grow(&[":work", ":review"])?;
+    assert_eq!("author@review.example.com", git_config("user.email"));
+    assert_eq!("true", git_config("commit.gpgsign"));
+
+    grow(&[":review", ":work"])?;
+    assert_eq!("author@work.example.com", git_config("user.email"));
+
+    Ok(())
+}
+
+/// `garden grow` applies multi-valued gitconfig settings and unsets null settings.
+#[test]
+#[named]
+fn grow_gitconfig_multi_valued() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let args = [
+        "--chdir",
+        &root,
+        "--config",
+        "tests/data/gitconfig.yaml",
+        "grow",
+        "work-repo",
+    ];
+    exec_garden(&args)?;
+    let worktree = fixture.worktree("work-repo");
+    let cmd = ["git", "config", "--get-all", "remote.origin.fetch"];
+    let expect = "+refs/heads/*:refs/remotes/origin/*\n+refs/tags/*:refs/tags/*";
+    assert_eq!(expect, assert_cmd_capture(&cmd, &worktree));
+    let cmd = ["git", "config", "branch.default.merge"];
+    assert_cmd_status(&cmd, &worktree, 1);
+
+    // Growing again leaves the settings as-is.
+    exec_garden(&args)?;
+    let cmd = ["git", "config", "--get-all", "remote.origin.fetch"];
+    assert_eq!(expect, assert_cmd_capture(&cmd, &worktree));
+
+    Ok(())
+}
+
+/// `garden grow --prune-remotes` and "remotes-exclusive: true" remove unconfigured remotes.
+#[test]
+#[named]
+fn grow_prune_remotes() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let grow = |args: &[&str]| {
+        let cmd = [
+            &[
+                "--chdir",
+                root.as_str(),
+                "--config",
+                "tests/data/remotes.yaml",
+                "grow",
+            ],
+            args,
+        ]
+        .concat();
+        garden_exit_status(&cmd)
+    };
+    assert_eq!(grow(&["pruned", "exclusive"]), errors::EX_OK);
+    let remotes = |tree: &str| assert_cmd_capture(&["git", "remote"], &fixture.worktree(tree));
+    for tree in ["pruned", "exclusive"] {
+        let worktree = fixture.worktree(tree);
+        for remote in ["old", "fork-a"] {
+            assert_cmd(&["git", "remote", "add", remote, "url"], &worktree);
+        }
+    }
+
+    // Unconfigured remotes are retained by default.
+    assert_eq!(grow(&["pruned"]), errors::EX_OK);
+    assert_eq!("fork-a\nold\norigin", remotes("pruned"));
+
+    // "--prune-remotes" removes unconfigured remotes except for "remotes-keep" patterns.
+    assert_eq!(grow(&["--prune-remotes", "pruned"]), errors::EX_OK);
+    assert_eq!("fork-a\norigin", remotes("pruned"));
+
+    // "remotes-exclusive: true" removes unconfigured remotes without "--prune-remotes".
+    assert_eq!(grow(&["exclusive"]), errors::EX_OK);
+    assert_eq!("origin", remotes("exclusive"));
+
+    Ok(())
+}
+
 /// This creates a worktree
 #[test]
 #[named]
@@ -396,6 +547,710 @@ fn grow_worktree_and_parent() -> Result<()> {
     Ok(())
 }
 
+/// `garden grow` creates worktrees that track the configured upstream branch and
+/// creates new branches from a base ref when the upstream branch does not exist.
+#[test]
+#[named]
+fn grow_worktree_upstream() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    exec_garden(&[
+        "--chdir",
+        &fixture.root(),
+        "--config",
+        "tests/data/worktree.yaml",
+        "grow",
+        "tracking",
+        "feature",
+    ])?;
+
+    // "branches.tracking" tracks "origin/dev".
+    let tracking = fixture.worktree("tracking");
+    assert_ref(&tracking, "tracking");
+    let cmd = ["git", "rev-parse", "--abbrev-ref", "tracking@{upstream}"];
+    assert_eq!("origin/dev", assert_cmd_capture(&cmd, &tracking));
+
+    // "origin/feature" does not exist so "feature" is created from "origin/dev".
+    let feature = fixture.worktree("feature");
+    assert_ref(&feature, "feature");
+    let cmd = ["git", "rev-parse", "HEAD"];
+    let dev_commit = assert_cmd_capture(&["git", "rev-parse", "origin/dev"], &feature);
+    assert_eq!(dev_commit, assert_cmd_capture(&cmd, &feature));
+    let cmd = ["git", "config", "branch.feature.remote"];
+    assert_eq!("origin", assert_cmd_capture(&cmd, &feature));
+    let cmd = ["git", "config", "branch.feature.merge"];
+    assert_eq!("refs/heads/feature", assert_cmd_capture(&cmd, &feature));
+
+    Ok(())
+}
+
+/// `garden grow --jobs` grows trees in parallel. Worktrees wait for their parent.
+#[test]
+#[named]
+fn grow_jobs() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    // garden grow --jobs 4 dev default
+    exec_garden(&[
+        "--chdir",
+        &fixture.root(),
+        "--config",
+        "tests/data/worktree.yaml",
+        "grow",
+        "--jobs",
+        "4",
+        "dev",
+        "default",
+    ])?;
+
+    let worktree_default = fixture.worktree("default");
+    let worktree_dev = fixture.worktree("dev");
+    assert_ref(&worktree_default, "default");
+    assert_ref(&worktree_dev, "dev");
+
+    // Independent trees are cloned in parallel.
+    exec_garden(&[
+        "--chdir",
+        &fixture.root(),
+        "--config",
+        "tests/data/garden.yaml",
+        "grow",
+        "-j",
+        "0",
+        "example/tree",
+        "example/shallow",
+    ])?;
+    assert_ref(&fixture.worktree("example/tree/repo"), "origin/dev");
+    assert_ref(&fixture.worktree("example/tree/shallow"), "origin/dev");
+
+    // Growing existing trees in parallel is a no-op.
+    exec_garden(&[
+        "--chdir",
+        &fixture.root(),
+        "--config",
+        "tests/data/worktree.yaml",
+        "grow",
+        "-j",
+        "2",
+        "dev",
+        "default",
+    ])?;
+    assert_ref(&worktree_dev, "dev");
+
+    Ok(())
+}
+
+/// `garden grow --fetch` fetches remotes and `garden grow --update` fast-forwards branches.
+#[test]
+#[named]
+fn grow_fetch_and_update() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let grow = |args: &[&str]| {
+        let root = fixture.root();
+        let mut cmd = vec![
+            "--chdir",
+            &root,
+            "--config",
+            "tests/data/worktree.yaml",
+            "grow",
+        ];
+        cmd.extend(args);
+        garden_exit_status(&cmd)
+    };
+    // Add a commit to the "default" branch in the upstream repository.
+    let upstream = fixture.path("repos/example.git");
+    let add_commit = |repository: &str, parent: &str, message: &str| {
+        let tree = format!("{parent}^{{tree}}");
+        let cmd = ["git", "commit-tree", "-m", message, "-p", parent, &tree];
+        let commit = assert_cmd_capture(&cmd, repository);
+        assert_cmd(&["git", "update-ref", "HEAD", &commit], repository);
+        commit
+    };
+
+    assert_eq!(grow(&["default"]), errors::EX_OK);
+    let worktree = fixture.worktree("default");
+    let commit = add_commit(&upstream, "default", "upstream");
+
+    // "--fetch" updates the remote branches but not the current branch.
+    assert_eq!(grow(&["--fetch", "default"]), errors::EX_OK);
+    let cmd = ["git", "rev-parse", "origin/default"];
+    assert_eq!(commit, assert_cmd_capture(&cmd, &worktree));
+    let cmd = ["git", "rev-parse", "HEAD"];
+    assert_ne!(commit, assert_cmd_capture(&cmd, &worktree));
+
+    // "--update" fast-forwards the current branch.
+    assert_eq!(grow(&["--update", "default"]), errors::EX_OK);
+    assert_eq!(commit, assert_cmd_capture(&cmd, &worktree));
+
+    // Diverged branches cannot be fast-forwarded and are reported.
+    add_commit(&worktree, "HEAD", "local");
+    add_commit(&upstream, "default", "diverged");
+    let root = fixture.root();
+    let args = [
+        "--chdir",
+        &root,
+        "--config",
+        "tests/data/worktree.yaml",
+        "grow",
+        "--update",
+        "default",
+    ];
+    assert_ne!(garden_exit_status(&args), errors::EX_OK);
+    let output = garden_capture_stderr(&args);
+    assert!(output.contains("unable to fast-forward"));
+    assert!(output.lines().any(|line| line.trim() == "default"));
+
+    Ok(())
+}
+
+/// `garden grow` initializes submodules for trees with "submodules: true".
+#[test]
+#[named]
+fn grow_submodules() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    // Create repos/submodules.git with a "vendor" submodule pointing to repos/example.git.
+    let example_git = std::fs::canonicalize(fixture.path("repos/example.git"))?;
+    let example_url = format!("file://{}", example_git.to_string_lossy());
+    assert_cmd(&["git", "init", "--quiet", "work"], &root);
+    let work = fixture.worktree("work");
+    let cmd = [
+        "git",
+        "-c",
+        "protocol.file.allow=always",
+        "submodule",
+        "add",
+        "--quiet",
+        &example_url,
+        "vendor",
+    ];
+    assert_cmd(&cmd, &work);
+    assert_cmd(&["git", "commit", "--quiet", "-m", "vendor"], &work);
+    let cmd = [
+        "git",
+        "clone",
+        "--quiet",
+        "--bare",
+        "work",
+        "repos/submodules.git",
+    ];
+    assert_cmd(&cmd, &root);
+
+    // Submodules are cloned using the "file" transport.
+    let grow = || {
+        let mut exec = std::process::Command::cargo_bin("garden").expect("garden not found");
+        exec.args([
+            "--chdir",
+            &root,
+            "--config",
+            "tests/data/garden.yaml",
+            "grow",
+            "example/submodules",
+        ]);
+        exec.env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+            .env("GIT_CONFIG_VALUE_0", "always");
+        exec.status().expect("unable to run garden").success()
+    };
+    // garden grow example/submodules
+    assert!(grow());
+    let vendor = fixture.worktree("example/submodules/vendor");
+    assert_ref(&vendor, "HEAD");
+
+    // Submodules are initialized in existing trees.
+    let cmd = ["git", "submodule", "deinit", "--quiet", "--force", "vendor"];
+    assert_cmd(&cmd, &fixture.worktree("example/submodules"));
+    assert!(!std::path::Path::new(&vendor).join(".git").exists());
+    assert!(grow());
+    fixture.worktree("example/submodules/vendor");
+
+    Ok(())
+}
+
+/// `garden grow` creates partial clones with sparse checkouts.
+#[test]
+#[named]
+fn grow_sparse_checkout() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    // Create repos/sparse.git with "docs" and "src" directories.
+    assert_cmd(&["git", "init", "--quiet", "work"], &root);
+    let work = fixture.worktree("work");
+    for path in ["docs", "src"] {
+        std::fs::create_dir(format!("{work}/{path}"))?;
+        std::fs::write(format!("{work}/{path}/README"), path)?;
+    }
+    std::fs::write(format!("{work}/README"), "top")?;
+    assert_cmd(&["git", "add", "."], &work);
+    assert_cmd(&["git", "commit", "--quiet", "-m", "sparse"], &work);
+    let cmd = [
+        "git",
+        "clone",
+        "--quiet",
+        "--bare",
+        "work",
+        "repos/sparse.git",
+    ];
+    assert_cmd(&cmd, &root);
+    let cmd = ["git", "config", "uploadpack.allowFilter", "true"];
+    assert_cmd(&cmd, &fixture.path("repos/sparse.git"));
+
+    // garden grow example/sparse
+    let args = [
+        "--chdir",
+        &root,
+        "--config",
+        "tests/data/garden.yaml",
+        "grow",
+        "example/sparse",
+    ];
+    exec_garden(&args)?;
+    let sparse = fixture.worktree("example/sparse");
+    let sparse_path = std::path::Path::new(&sparse);
+    assert!(sparse_path.join("README").exists());
+    assert!(sparse_path.join("docs/README").exists());
+    assert!(!sparse_path.join("src").exists());
+    // The partial clone filter is recorded in the repository.
+    let cmd = ["git", "config", "remote.origin.partialclonefilter"];
+    assert_eq!("blob:none", assert_cmd_capture(&cmd, &sparse));
+
+    // The sparse checkout is re-applied to existing trees.
+    assert_cmd(&["git", "sparse-checkout", "disable"], &sparse);
+    assert!(sparse_path.join("src/README").exists());
+    exec_garden(&args)?;
+    assert!(sparse_path.join("docs/README").exists());
+    assert!(!sparse_path.join("src").exists());
+
+    Ok(())
+}
+
+/// `garden grow` clones using mirrors from "garden.cache".
+#[test]
+#[named]
+fn grow_cache() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let grow = |query: &str| {
+        garden_exit_status(&[
+            "--chdir",
+            &root,
+            "--config",
+            "tests/data/cache.yaml",
+            "grow",
+            "--jobs",
+            "2",
+            query,
+        ])
+    };
+    // Both trees share a single mirror of repos/example.git.
+    assert_eq!(grow("cached*"), errors::EX_OK);
+    let cache = std::fs::canonicalize(fixture.path("cache"))?;
+    let alternates = std::fs::read_to_string(format!(
+        "{}/.git/objects/info/alternates",
+        fixture.worktree("cached")
+    ))?;
+    assert!(alternates.starts_with(cache.to_string_lossy().as_ref()));
+    fixture.worktree("copy");
+
+    // garden cache update refreshes the mirrors.
+    let status = garden_exit_status(&[
+        "--chdir",
+        &root,
+        "--config",
+        "tests/data/cache.yaml",
+        "cache",
+        "update",
+    ]);
+    assert_eq!(status, errors::EX_OK);
+
+    // Trees are cloned from the mirror when the remote is unavailable.
+    std::fs::remove_dir_all(fixture.path("cached"))?;
+    std::fs::rename(
+        fixture.path("repos/example.git"),
+        format!("{root}/repos/moved.git"),
+    )?;
+    assert_eq!(grow("cached"), errors::EX_OK);
+    let worktree = fixture.worktree("cached");
+    assert_ref(&worktree, "origin/dev");
+    let cmd = ["git", "config", "remote.origin.url"];
+    assert!(assert_cmd_capture(&cmd, &worktree).ends_with("/repos/example.git"));
+
+    Ok(())
+}
+
+/// `garden grow` checks out the tag or commit that a tree is pinned to
+/// and `garden inspect` flags trees whose HEAD differs from the pin.
+#[test]
+#[named]
+fn grow_pinned() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let upstream = fixture.path("repos/example.git");
+    // Tag the "default" branch and then add a commit after the tag.
+    assert_cmd(&["git", "tag", "v1.0", "default"], &upstream);
+    let tagged_commit = assert_cmd_capture(&["git", "rev-parse", "default"], &upstream);
+    let cmd = [
+        "git",
+        "commit-tree",
+        "-m",
+        "after v1.0",
+        "-p",
+        "default",
+        "default^{tree}",
+    ];
+    let latest_commit = assert_cmd_capture(&cmd, &upstream);
+    assert_cmd(
+        &["git", "update-ref", "refs/heads/default", &latest_commit],
+        &upstream,
+    );
+    let dev_commit = assert_cmd_capture(&["git", "rev-parse", "dev"], &upstream);
+
+    let pinned_commit = format!("pinned_commit={dev_commit}");
+    let base = [
+        "--chdir",
+        &root,
+        "--config",
+        "tests/data/pinned.yaml",
+        "--define",
+        &pinned_commit,
+    ];
+
+    // garden grow checks out the pinned revisions with a detached HEAD.
+    let args = [&base[..], &["grow", "tagged", "committed"]].concat();
+    assert_eq!(garden_exit_status(&args), errors::EX_OK);
+    let head = ["git", "rev-parse", "HEAD"];
+    let tagged = fixture.worktree("tagged");
+    assert_eq!(tagged_commit, assert_cmd_capture(&head, &tagged));
+    let committed = fixture.worktree("committed");
+    assert_eq!(dev_commit, assert_cmd_capture(&head, &committed));
+    let symbolic_ref = ["git", "symbolic-ref", "--quiet", "HEAD"];
+    assert_cmd_status(&symbolic_ref, &tagged, 1);
+
+    // garden inspect flags trees whose HEAD differs from the pin.
+    let args = [&base[..], &["inspect", "tagged"]].concat();
+    assert!(!garden_capture(&args).contains("HEAD differs"));
+    assert_cmd(&["git", "checkout", "--quiet", "origin/default"], &tagged);
+    assert!(garden_capture(&args).contains("HEAD differs from tag v1.0"));
+
+    // garden grow --update restores the pinned revision.
+    let args = [&base[..], &["grow", "--update", "tagged"]].concat();
+    assert_eq!(garden_exit_status(&args), errors::EX_OK);
+    assert_eq!(tagged_commit, assert_cmd_capture(&head, &tagged));
+
+    Ok(())
+}
+
+/// `garden lock` records the state of trees in "garden.lock"
+/// and `garden grow --locked` reproduces it.
+#[test]
+#[named]
+fn lock_and_grow_locked() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    // "garden.lock" is written next to the garden file.
+    let config = format!("{root}/garden.yaml");
+    std::fs::copy("tests/data/worktree.yaml", &config)?;
+    let garden = |args: &[&str]| {
+        let cmd = [
+            &["--chdir", root.as_str(), "--config", config.as_str()],
+            args,
+        ]
+        .concat();
+        garden_exit_status(&cmd)
+    };
+
+    assert_eq!(garden(&["grow", "default"]), errors::EX_OK);
+    let worktree = fixture.worktree("default");
+    let head = ["git", "rev-parse", "HEAD"];
+    let locked_commit = assert_cmd_capture(&head, &worktree);
+    assert_eq!(garden(&["lock", "default"]), errors::EX_OK);
+
+    let locked = garden::cmds::lock::read(std::path::Path::new(&fixture.path("garden.lock")))?;
+    let locked_tree = locked.get("default").context("default")?;
+    assert_eq!(locked_commit, locked_tree.commit);
+    assert_eq!("default", locked_tree.branch);
+    assert!(locked_tree.url.ends_with("/repos/example.git"));
+
+    // Advance the upstream branch and update the tree.
+    let upstream = fixture.path("repos/example.git");
+    let cmd = [
+        "git",
+        "commit-tree",
+        "-m",
+        "after lock",
+        "-p",
+        "default",
+        "default^{tree}",
+    ];
+    let commit = assert_cmd_capture(&cmd, &upstream);
+    assert_cmd(
+        &["git", "update-ref", "refs/heads/default", &commit],
+        &upstream,
+    );
+    assert_eq!(garden(&["grow", "--update", "default"]), errors::EX_OK);
+    assert_eq!(commit, assert_cmd_capture(&head, &worktree));
+
+    // garden grow --locked checks out the locked branch at the locked commit
+    // in existing and new trees.
+    let branch = ["git", "symbolic-ref", "--short", "HEAD"];
+    assert_eq!(garden(&["grow", "--locked", "default"]), errors::EX_OK);
+    assert_eq!(locked_commit, assert_cmd_capture(&head, &worktree));
+    assert_eq!("default", assert_cmd_capture(&branch, &worktree));
+    std::fs::remove_dir_all(&worktree)?;
+    assert_eq!(garden(&["grow", "--locked", "default"]), errors::EX_OK);
+    assert_eq!(locked_commit, assert_cmd_capture(&head, &worktree));
+    assert_eq!("default", assert_cmd_capture(&branch, &worktree));
+
+    Ok(())
+}
+
+/// `garden status` reports the branch, tracking and worktree status of trees.
+#[test]
+#[named]
+fn status_trees() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let garden = |args: &[&str]| {
+        let cmd = [
+            &[
+                "--chdir",
+                root.as_str(),
+                "--config",
+                "tests/data/worktree.yaml",
+            ],
+            args,
+        ]
+        .concat();
+        garden_capture(&cmd)
+    };
+    garden(&["grow", "dev"]);
+
+    // Add a stash entry and an untracked file to the "default" tree.
+    let worktree = fixture.worktree("default");
+    std::fs::write(format!("{worktree}/stashed.txt"), "stashed")?;
+    assert_cmd(&["git", "add", "stashed.txt"], &worktree);
+    assert_cmd(&["git", "stash", "--quiet"], &worktree);
+    std::fs::write(format!("{worktree}/untracked.txt"), "untracked")?;
+    // Advance the upstream "default" branch so that "default" is behind by one commit.
+    let upstream = fixture.path("repos/example.git");
+    let cmd = [
+        "git",
+        "commit-tree",
+        "-m",
+        "status",
+        "-p",
+        "default",
+        "default^{tree}",
+    ];
+    let commit = assert_cmd_capture(&cmd, &upstream);
+    assert_cmd(
+        &["git", "update-ref", "refs/heads/default", &commit],
+        &upstream,
+    );
+    assert_cmd(&["git", "fetch", "--quiet", "origin"], &worktree);
+
+    let output = garden(&["status", "default", "dev", "tracking"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(3, lines.len());
+    assert_eq!(
+        "+ default  default [behind 1] untracked 1, stashes 1",
+        lines[0]
+    );
+    // Stashes are shared by all of the worktrees in a repository.
+    assert!(lines[1].starts_with("+ dev  dev stashes 1 (worktree of "));
+    assert_eq!("- tracking  (missing)", lines[2]);
+
+    // The current branch is reported when it differs from the configured branch.
+    assert_cmd(
+        &["git", "checkout", "--quiet", "--detach"],
+        &fixture.worktree("dev"),
+    );
+    let output = garden(&["status", "dev"]);
+    assert!(output.starts_with("+ dev  (detached) (configured: dev) stashes 1 (worktree of "));
+
+    Ok(())
+}
+
+/// `garden check` reports differences between trees and the configuration
+/// and `garden check --fix` reconciles them.
+#[test]
+#[named]
+fn check_and_fix_drift() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let garden_args = |args: &[&str]| -> Vec<String> {
+        let cmd = [
+            &[
+                "--chdir",
+                root.as_str(),
+                "--config",
+                "tests/data/worktree.yaml",
+            ],
+            args,
+        ]
+        .concat();
+        cmd.iter().map(|arg| arg.to_string()).collect()
+    };
+    let garden = |args: &[&str]| {
+        let cmd = garden_args(args);
+        let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
+        garden_capture(&cmd)
+    };
+    let check_status = |args: &[&str]| {
+        let cmd = garden_args(&[&["check"], args].concat());
+        let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
+        garden_exit_status(&cmd)
+    };
+    garden(&["grow", "dev"]);
+    assert_eq!("", garden(&["check", "default", "dev"]));
+    assert_eq!(errors::EX_OK, check_status(&["default", "dev"]));
+
+    // Change the "origin" URL and detach HEAD in the "dev" worktree.
+    assert_cmd(
+        &["git", "remote", "set-url", "origin", "file:///moved.git"],
+        &fixture.worktree("default"),
+    );
+    assert_cmd(
+        &["git", "checkout", "--quiet", "--detach"],
+        &fixture.worktree("dev"),
+    );
+    let output = garden(&["check", "default", "dev", "tracking"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[0]
+        .starts_with("! default: remote origin url is \"file:///moved.git\", expected \"file:///"));
+    assert_eq!("! dev: HEAD is detached, expected branch dev", lines[1]);
+    assert_eq!("! tracking: tree is missing", lines[2]);
+    assert_eq!(
+        errors::EX_ERROR,
+        check_status(&["default", "dev", "tracking"])
+    );
+
+    // "--fix" reconciles the differences.
+    // Output from the commands run by "garden grow" is omitted.
+    let output = garden(&["--quiet", "check", "--fix", "default", "dev", "tracking"]);
+    let lines: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("+ "))
+        .collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("+ default: remote origin url is "));
+    assert!(lines[0].ends_with(" (fixed)"));
+    assert_eq!(
+        "+ dev: HEAD is detached, expected branch dev (fixed)",
+        lines[1]
+    );
+    assert_eq!("+ tracking: tree is missing (fixed)", lines[2]);
+    assert_eq!("", garden(&["check", "default", "dev", "tracking"]));
+    assert_eq!(errors::EX_OK, check_status(&["default", "dev", "tracking"]));
+
+    Ok(())
+}
+
+/// `garden check --fix` grows missing trees using their resolved tree context.
+#[test]
+#[named]
+fn check_fix_grows_resolved_trees() -> Result<()> {
+    let fixture = BareRepoFixture::new(function_name!());
+    let root = fixture.root();
+    let garden = |args: &[&str]| {
+        let cmd = [
+            &[
+                "--chdir",
+                root.as_str(),
+                "--config",
+                "tests/data/check.yaml",
+                "--quiet",
+                "check",
+                "--fix",
+            ],
+            args,
+        ]
+        .concat();
+        garden_capture(&cmd)
+    };
+    // The "shared" tree is grown rather than the trees from the "shared" group,
+    // and the gitconfig settings from the "ci" garden are applied.
+    let output = garden(&[":ci"]);
+    assert!(output.ends_with("+ shared: tree is missing (fixed)"));
+    assert!(!fixture.root_pathbuf().join("repo").exists());
+    assert_eq!(
+        "garden",
+        assert_cmd_capture(
+            &["git", "config", "check.scope"],
+            &fixture.worktree("shared")
+        )
+    );
+
+    // Trees without an "origin" remote cannot be grown.
+    let output = garden(&["local-only"]);
+    assert_eq!(
+        "! local-only: tree is missing and has no \"origin\" remote (not fixable)",
+        output
+    );
+    assert!(!fixture.root_pathbuf().join("local-only").exists());
+    let cmd = [
+        "--chdir",
+        root.as_str(),
+        "--config",
+        "tests/data/check.yaml",
+        "check",
+        "--fix",
+        "local-only",
+    ];
+    assert_eq!(errors::EX_ERROR, garden_exit_status(&cmd));
+
+    Ok(())
+}
+
+/// `garden query`, `garden inspect` and `garden list` emit structured documents.
+#[test]
+fn query_inspect_and_list_formats() -> Result<()> {
+    let garden = |args: &[&str]| {
+        let cmd = [&["--config", "tests/data/garden.yaml"], args].concat();
+        garden_capture(&cmd)
+    };
+    let load = |output: &str| -> Result<yaml_rust::Yaml> {
+        let mut docs = yaml_rust::YamlLoader::load_from_str(output)?;
+        Ok(docs.remove(0))
+    };
+
+    // Text output lists one tree per line.
+    let output = garden(&["query", "example/s*"]);
+    assert_eq!(
+        "example/submodules\nexample/sparse\nexample/shallow\nexample/single-branch",
+        output
+    );
+
+    // JSON output contains the resolved tree contexts.
+    let doc = load(&garden(&["query", "--format", "json", ":dev"]))?;
+    let trees = doc["trees"].as_vec().context("trees")?;
+    assert_eq!(1, trees.len());
+    assert_eq!(Some("example/tree"), trees[0]["tree"].as_str());
+    assert_eq!(Some("dev"), trees[0]["garden"].as_str());
+    assert!(trees[0]["group"].is_null());
+    assert!(trees[0]["config"].as_i64().is_some());
+    let path = trees[0]["path"].as_str().context("path")?;
+    assert!(path.ends_with("example/tree/repo"));
+
+    // YAML output from "garden inspect" includes existence and symlink details.
+    let doc = load(&garden(&["inspect", "--format", "yaml", "example/link"]))?;
+    let tree = &doc["trees"][0];
+    assert_eq!(Some("example/link"), tree["tree"].as_str());
+    assert_eq!(Some(false), tree["exists"].as_bool());
+    let symlink = tree["symlink"].as_str().context("symlink")?;
+    assert!(symlink.ends_with("example/tree/repo"));
+    assert!(tree["worktree"].is_null());
+    assert!(tree["commands"].as_vec().is_some());
+
+    // JSON output from "garden list" includes gardens, groups, trees and commands.
+    let doc = load(&garden(&["list", "--format", "json"]))?;
+    assert!(doc["gardens"]["dev"]["trees"].as_vec().is_some());
+    assert!(doc["groups"].as_hash().is_some());
+    assert!(doc["trees"]["example/tree"]["path"].as_str().is_some());
+    assert!(doc["commands"].as_hash().is_some());
+
+    Ok(())
+}
+
 /// `garden eval` evaluates ${GARDEN_CONFIG_DIR}
 #[test]
 #[named]
@@ -694,6 +1549,760 @@ fn cmd_breadth_first_and_depth_first() {
     assert_eq!(expect, actual);
 }
 
+/// Test "garden cmd --jobs" and "garden <custom> --jobs"
+#[test]
+#[named]
+fn cmd_jobs() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+
+    // Commands run in parallel across trees. Output from separate trees can arrive
+    // in any order but each tree runs its commands in order.
+    let actual = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--jobs",
+        "2",
+        "trees",
+        "tree-name",
+        "tree-var",
+    ]);
+    let lines: Vec<&str> = actual.lines().collect();
+    let mut sorted = lines.clone();
+    sorted.sort();
+    assert_eq!(sorted, vec!["tree1", "tree2", "x1", "x2"]);
+    let position = |value: &str| lines.iter().position(|line| *line == value);
+    assert!(position("tree1") < position("x1"));
+    assert!(position("tree2") < position("x2"));
+
+    // Breadth-first runs each command in all trees before running the next command.
+    let actual = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--breadth-first",
+        "-j",
+        "0",
+        "trees",
+        "tree-name",
+        "tree-var",
+    ]);
+    let lines: Vec<&str> = actual.lines().collect();
+    assert_eq!(lines.len(), 4);
+    let mut first = lines[..2].to_vec();
+    first.sort();
+    let mut second = lines[2..].to_vec();
+    second.sort();
+    assert_eq!(first, vec!["tree1", "tree2"]);
+    assert_eq!(second, vec!["x1", "x2"]);
+
+    // --keep-going runs the command in every tree.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "error-command",
+        "--jobs",
+        "2",
+        "--keep-going",
+        "tree1",
+        "tree2",
+    ]);
+    assert_eq!(output, "ok\nok");
+}
+
+/// Test "garden cmd --output=prefix" and "garden cmd --output=block"
+#[test]
+fn cmd_output_modes() {
+    // Each line is prefixed by the tree name.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "cmd",
+        "--output",
+        "prefix",
+        "trees",
+        "tree-name",
+        "tree-var",
+    ]);
+    assert_eq!(output, "tree1: tree1\ntree1: x1\ntree2: tree2\ntree2: x2");
+
+    // Output from each tree is displayed as a single block even when running in parallel.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "cmd",
+        "--output",
+        "block",
+        "--jobs",
+        "2",
+        "trees",
+        "tree-name",
+        "tree-var",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    let mut blocks = vec![lines[..2].join(" "), lines[2..].join(" ")];
+    blocks.sort();
+    assert_eq!(blocks, vec!["tree1 x1", "tree2 x2"]);
+
+    // "garden exec" supports the same output modes.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "exec",
+        "--output",
+        "prefix",
+        "trees",
+        "echo",
+        "hello",
+    ]);
+    assert_eq!(output, "tree1: hello\ntree2: hello");
+}
+
+/// Test "garden cmd --dry-run" and "garden exec --dry-run"
+#[test]
+fn cmd_dry_run() {
+    // Commands are printed but not run.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "cmd",
+        "--dry-run",
+        "dry-run-env",
+        "error-command",
+        "tree-name",
+        "--",
+        "a",
+        "b c",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[0].starts_with(": cd "));
+    assert_eq!(
+        lines[1],
+        ": export GARDEN_DRY_RUN_VALUE=\"dry-run-env value\""
+    );
+    assert!(lines[2].contains(" -e -c "));
+    assert!(!output.contains("\nok"));
+    assert!(lines[lines.len() - 1].contains(" -e -c \"echo \\\"dry-run-env\\\"\" "));
+    assert!(lines[lines.len() - 1].ends_with(" a \"b c\""));
+
+    // --no-errexit omits "-e".
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "cmd",
+        "--dry-run",
+        "--no-errexit",
+        "dry-run-env",
+        "tree-name",
+    ]);
+    assert!(output.contains(" -c \"echo \\\"dry-run-env\\\"\" "));
+    assert!(!output.contains(" -e "));
+
+    // "garden exec --dry-run" prints the command.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "exec",
+        "--dry-run",
+        "dry-run-env",
+        "false",
+        "value",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(": cd "));
+    assert_eq!(
+        lines[1],
+        ": export GARDEN_DRY_RUN_VALUE=\"dry-run-env value\""
+    );
+    assert!(lines[2].ends_with("false value"));
+}
+
+/// Test commands defined using the structured mapping syntax.
+#[test]
+fn cmd_structured() {
+    // "cwd" is relative to the tree and "env" is added to the command environment.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "structured",
+        "dry-run-env",
+    ]);
+    assert_eq!(output, "includes\ndry-run-env structured");
+
+    // Dry-run mode displays the command's directory, environment and shell.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "structured",
+        "--dry-run",
+        "dry-run-env",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with(": cd ") && line.ends_with("includes")));
+    assert!(lines.contains(&": export GARDEN_STRUCTURED=\"dry-run-env structured\""));
+    assert!(lines[lines.len() - 1].starts_with(": sh -e -c "));
+
+    // Descriptions are displayed by "garden list --commands".
+    let output = garden_capture(&["--chdir", "tests/data", "list", "--commands"]);
+    assert!(output.contains("- structured: Display the working directory and command environment"));
+}
+
+/// Test commands with declared parameters.
+#[test]
+fn cmd_params() {
+    // Default values are used when options are not specified.
+    let output = garden_capture(&["--chdir", "tests/data", "--quiet", "params", "tree1"]);
+    assert_eq!(output, "staging 1 false");
+
+    // Parameters are specified using options.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "params",
+        "-t",
+        "production",
+        "--count",
+        "3",
+        "--verbose",
+        "tree1",
+    ]);
+    assert_eq!(output, "production 3 true");
+
+    // Variables defined using "-D" take precedence over default values.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "--define",
+        "target=qa",
+        "params",
+        "tree1",
+    ]);
+    assert_eq!(output, "qa 1 false");
+
+    // Default values are available to "garden cmd".
+    let output = garden_capture(&["--chdir", "tests/data", "--quiet", "cmd", "tree1", "params"]);
+    assert_eq!(output, "staging 1 false");
+
+    // Integer parameters are validated.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "params",
+        "--count",
+        "many",
+        "tree1",
+    ]);
+    assert_ne!(status, errors::EX_OK);
+
+    // Parameters are displayed by "garden <command> --help".
+    let output = garden_capture(&["--chdir", "tests/data", "params", "--help"]);
+    assert!(output.contains("--target <VALUE>"));
+    assert!(output.contains("Deployment target"));
+}
+
+/// Test "garden cmd --timeout" and the "timeout" command setting.
+#[test]
+#[named]
+fn cmd_timeout() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+
+    // Commands that exceed their timeout are killed and return a distinct exit status.
+    let start = std::time::Instant::now();
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "slow",
+        "dry-run-env",
+    ]);
+    assert_eq!(status, errors::EX_TIMEOUT);
+    assert!(start.elapsed() < std::time::Duration::from_secs(8));
+
+    // --timeout overrides the command's timeout setting.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--timeout",
+        "30",
+        "dry-run-env",
+        "tree-name",
+    ]);
+    assert_eq!(status, errors::EX_OK);
+}
+
+/// Processes started by commands that time out are killed along with the command.
+#[test]
+#[named]
+fn cmd_timeout_kills_child_processes() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+    let leaked = fixture.pathbuf("leaked");
+    std::fs::remove_file(&leaked).unwrap_or(());
+
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--output",
+        "prefix",
+        "dry-run-env",
+        "slow-children",
+        "--",
+        &leaked.to_string_lossy(),
+    ]);
+    assert_eq!(status, errors::EX_TIMEOUT);
+    // The background process would have written to the file after two seconds.
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    assert!(!leaked.exists());
+}
+
+/// Test "garden cmd --retries" and the "retries" command setting.
+#[test]
+#[named]
+fn cmd_retries() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+    let counter = fixture.pathbuf("counter");
+    let counter = counter.to_string_lossy();
+
+    // The "flaky" command succeeds on the third attempt.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "flaky",
+        "dry-run-env",
+        "--",
+        &counter,
+    ]);
+    assert_eq!(output, "");
+    let attempts = std::fs::read_to_string(counter.as_ref()).unwrap_or_default();
+    assert_eq!(attempts.lines().count(), 3);
+
+    // --retries overrides the command's retries setting.
+    std::fs::remove_file(counter.as_ref()).unwrap_or(());
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "flaky",
+        "--retries",
+        "1",
+        "--retry-delay",
+        "0",
+        "dry-run-env",
+        "--",
+        &counter,
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let attempts = std::fs::read_to_string(counter.as_ref()).unwrap_or_default();
+    assert_eq!(attempts.lines().count(), 2);
+}
+
+/// Test "garden cmd --report" and "--report-format".
+#[test]
+#[named]
+fn cmd_report() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+    let report_json = format!("{root}/report.json");
+    let report_xml = format!("{root}/report.xml");
+
+    // Each (tree, command) pair is recorded in the JSON report.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--keep-going",
+        "--report",
+        &report_json,
+        "trees",
+        "error-command",
+        "tree-name",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let json = std::fs::read_to_string(format!("{root}/report.json")).unwrap_or_default();
+    let lines: Vec<&str> = json.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert!(lines[2].starts_with(
+        "    {\"query\": \"trees\", \"tree\": \"tree1\", \"garden\": null, \"command\": \"error-command\", \"status\": 1, "
+    ));
+    assert!(lines[3].contains(
+        "\"tree\": \"tree1\", \"garden\": null, \"command\": \"tree-name\", \"status\": 0, "
+    ));
+    assert!(lines[4].contains(
+        "\"tree\": \"tree2\", \"garden\": null, \"command\": \"error-command\", \"status\": 1, "
+    ));
+    assert!(lines[5].contains(
+        "\"tree\": \"tree2\", \"garden\": null, \"command\": \"tree-name\", \"status\": 0, "
+    ));
+
+    // JUnit reports contain one test suite per command and one test case per tree.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--keep-going",
+        "--report",
+        &report_xml,
+        "--report-format",
+        "junit",
+        "trees",
+        "error-command",
+        "tree-name",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let xml = std::fs::read_to_string(format!("{root}/report.xml")).unwrap_or_default();
+    assert!(xml.contains("<testsuites name=\"garden\" tests=\"4\" failures=\"2\" "));
+    assert!(xml.contains("<testsuite name=\"error-command\" tests=\"2\" failures=\"2\" "));
+    assert!(xml.contains("<testsuite name=\"tree-name\" tests=\"2\" failures=\"0\" "));
+    assert_eq!(
+        xml.matches("<failure message=\"exit status 1\"/>").count(),
+        2
+    );
+
+    // Commands that did not run because a dependency failed are reported as skipped.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--report",
+        &report_xml,
+        "--report-format",
+        "junit",
+        "dry-run-env",
+        "marker-build",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let xml = std::fs::read_to_string(format!("{root}/report.xml")).unwrap_or_default();
+    assert!(xml.contains("<testsuites name=\"garden\" tests=\"2\" failures=\"1\" skipped=\"1\" "));
+    assert!(
+        xml.contains("<testsuite name=\"marker-build\" tests=\"1\" failures=\"0\" skipped=\"1\" ")
+    );
+    assert!(xml.contains("<skipped message=\"a required command failed\"/>"));
+}
+
+/// Test "garden cmd --failed" and "garden <command> --rerun-failed".
+#[test]
+#[named]
+fn cmd_failed() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+
+    // Failed commands are recorded in the garden root's state directory.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--keep-going",
+        "dry-run-env",
+        "marker",
+        "tree-name",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let failed = std::fs::read_to_string(format!("{root}/.garden/failed.json"));
+    assert!(failed
+        .unwrap_or_default()
+        .contains("\"command\": \"marker\""));
+
+    // Only the failed commands are re-run.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--failed",
+        "--dry-run",
+    ]);
+    assert!(output.contains("test -e marker"));
+    assert!(!output.contains("TREE_NAME"));
+    assert!(!output.contains("echo"));
+
+    // Commands that succeed are removed from the failed list.
+    std::fs::write(fixture.pathbuf("marker"), "").unwrap_or(());
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "marker",
+        "--rerun-failed",
+    ]);
+    assert_eq!(status, errors::EX_OK);
+    let failed = std::fs::read_to_string(format!("{root}/.garden/failed.json"));
+    assert!(!failed.unwrap_or_default().contains("\"command\""));
+
+    // Nothing is run when there are no failed commands.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--failed",
+        "--dry-run",
+    ]);
+    assert_eq!(output, "");
+
+    // Corrupt failure records are reported as errors.
+    std::fs::write(fixture.pathbuf(".garden/failed.json"), "{\"results\": [").unwrap_or(());
+    let args = [
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "cmd",
+        "--failed",
+        "--dry-run",
+    ];
+    assert_eq!(garden_exit_status(&args), errors::EX_DATAERR);
+    assert!(garden_capture_stderr(&args).contains("failed.json"));
+}
+
+/// Commands skipped because a dependency failed are re-run by "--failed".
+#[test]
+#[named]
+fn cmd_failed_dependency() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+
+    // The dependent command is reported as skipped and recorded as failed.
+    let report = fixture.pathbuf("report.json");
+    let report_str = report.to_string_lossy();
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--keep-going",
+        "--report",
+        &report_str,
+        "dry-run-env",
+        "marker-build",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let report = std::fs::read_to_string(&report).unwrap_or_default();
+    assert!(report.contains("\"command\": \"marker-build\", \"status\": -1"));
+    let failed = std::fs::read_to_string(format!("{root}/.garden/failed.json"));
+    let failed = failed.unwrap_or_default();
+    assert!(failed.contains("\"command\": \"marker\""));
+    assert!(failed.contains("\"command\": \"marker-build\""));
+
+    // Dependents are also recorded when commands stop at the first failure.
+    std::fs::remove_file(format!("{root}/.garden/failed.json")).unwrap_or(());
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "dry-run-env",
+        "marker-build",
+    ]);
+    assert_eq!(status, errors::EX_ERROR);
+    let failed = std::fs::read_to_string(format!("{root}/.garden/failed.json"));
+    assert!(failed
+        .unwrap_or_default()
+        .contains("\"command\": \"marker-build\""));
+
+    // The originally requested command is re-run along with its dependency.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--failed",
+        "--dry-run",
+    ]);
+    assert!(output.contains("test -e marker"));
+    assert!(output.contains("echo built"));
+}
+
+/// Test commands with "when" predicates.
+#[test]
+fn cmd_when() {
+    // Trees that do not satisfy all of the conditions are skipped.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "when-tree-var",
+        "tree1",
+        "tree2",
+        "dry-run-env",
+    ]);
+    assert_eq!(output, "tree1");
+
+    // Skipped trees do not print a header.
+    let output = garden_capture_stderr(&[
+        "--chdir",
+        "tests/data",
+        "cmd",
+        "dry-run-env",
+        "when-missing",
+    ]);
+    assert_eq!(output, "");
+
+    // Trees are not skipped when other commands run in the tree.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--quiet",
+        "cmd",
+        "tree1",
+        "when-missing",
+        "tree-name",
+    ]);
+    assert_eq!(output, "tree1");
+}
+
+/// Test command dependencies and pre/post hooks.
+#[test]
+#[named]
+fn cmd_depends() {
+    let fixture = TmpRootFixture::new(function_name!());
+    let root = fixture.root_abs();
+
+    // Dependencies run first followed by the pre hooks, the command and post hooks.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "graph/build",
+        "tree1",
+    ]);
+    assert_eq!(output, "fetch\ngenerate\npre\nbuild\nnotify");
+
+    // Commands with only dependencies do not need to define "run".
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "graph/all",
+        "tree1",
+    ]);
+    assert_eq!(output, "fetch\ngenerate\npre\nbuild\nnotify");
+
+    // Dependencies run once per tree.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "cmd",
+        "--breadth-first",
+        "trees",
+        "graph/generate",
+        "graph/build",
+    ]);
+    assert_eq!(
+        output,
+        "fetch\ngenerate\nfetch\ngenerate\npre\nbuild\nnotify\npre\nbuild\nnotify"
+    );
+
+    // Commands do not run when their dependencies fail.
+    let output = garden_capture(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "--quiet",
+        "graph/fail",
+        "tree1",
+    ]);
+    assert_eq!(output, "ok");
+
+    // Cycles are reported as configuration errors.
+    let status = garden_exit_status(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "graph/cycle-a",
+        "tree1",
+    ]);
+    assert_eq!(status, errors::EX_CONFIG);
+    let output = garden_capture_stderr(&[
+        "--chdir",
+        "tests/data",
+        "--root",
+        &root,
+        "graph/cycle-a",
+        "tree1",
+    ]);
+    assert!(output.contains("graph/cycle-a -> graph/cycle-b -> graph/cycle-a"));
+
+    // Dependencies must name commands that exist.
+    let status = garden_exit_status(&["--chdir", "tests/data", "graph/unknown", "tree1"]);
+    assert_eq!(status, errors::EX_CONFIG);
+    let output = garden_capture_stderr(&["--chdir", "tests/data", "graph/unknown", "tree1"]);
+    assert!(output.contains("unknown command in depends: graph/fecth"));
+}
+
 /// Test -n / --no-errexit and the shell "-e" behavior.
 #[test]
 fn cmd_no_errexit() {
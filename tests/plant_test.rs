@@ -137,6 +137,29 @@ fn plant_bare_repo() -> Result<()> {
     Ok(())
 }
 
+/// `garden plant` detects repositories with submodules.
+#[test]
+#[named]
+fn plant_submodules() -> Result<()> {
+    let fixture = common::BareRepoFixture::new(function_name!());
+    // Create an empty garden.yaml using "garden init".
+    common::exec_garden(&["--chdir", &fixture.root(), "init"])?;
+
+    let cmd = ["git", "init", "--quiet", "repo"];
+    common::assert_cmd(&cmd, &fixture.root());
+    std::fs::write(fixture.worktree("repo") + "/.gitmodules", "")?;
+
+    common::exec_garden(&["--chdir", &fixture.root(), "plant", "repo"])?;
+
+    let garden_yaml = fixture.path("garden.yaml");
+    let path = Some(std::path::PathBuf::from(&garden_yaml));
+    let cfg = garden::config::new(&path, &None, 0, None)?;
+    assert_eq!(1, cfg.trees.len());
+    assert_eq!(garden::model::Submodules::Enabled, cfg.trees[0].submodules);
+
+    Ok(())
+}
+
 /// `garden plant` detects "git worktree" repositories.
 #[test]
 #[named]
@@ -60,6 +60,58 @@ fn resolve_trees_group_with_wildcards() {
     assert_eq!("annex/local", result[1].tree);
 }
 
+#[test]
+fn resolve_trees_set_operators() {
+    let config = common::garden_config();
+    let tree_names = |query: &str| -> Vec<String> {
+        garden::query::resolve_trees(&config, query)
+            .into_iter()
+            .map(|ctx| ctx.tree)
+            .collect()
+    };
+    // Unions are returned in the order in which trees are first matched without duplicates.
+    assert_eq!(vec!["git", "cola"], tree_names("@git,@cola,@git"));
+    assert_eq!(
+        vec!["cola", "git", "python/qtpy", "annex/data"],
+        tree_names("%reverse, %cola, annex-1")
+    );
+    // Intersections retain the order of the trees on the left.
+    assert_eq!(vec!["git", "cola"], tree_names(":cola & %reverse"));
+    assert_eq!(vec!["cola", "git"], tree_names("%reverse & :cola"));
+    // Exclusion requires whitespace so that names containing dashes can be used.
+    assert_eq!(vec!["git", "cola"], tree_names("%cola - python/*"));
+    assert_eq!(vec!["annex/data"], tree_names("%annex - annex-2"));
+    // "&" and " - " are applied from left to right before the "," union.
+    assert_eq!(
+        vec!["cola", "python/qtpy", "oneline"],
+        tree_names("%cola - @git & :cola, oneline")
+    );
+    assert!(tree_names("%cola & %annex").is_empty());
+
+    // Trees resolved from a garden retain the garden context.
+    let result = garden::query::resolve_trees(&config, ":cola & %reverse");
+    assert_eq!(Some(string!("cola")), result[0].garden);
+    assert_eq!(Some(string!("cola")), result[0].group);
+
+    // Trees are returned once for each garden context that they are matched in.
+    let result = garden::query::resolve_trees(&config, ":cola,:git,:cola");
+    let contexts: Vec<(&str, Option<&str>)> = result
+        .iter()
+        .map(|ctx| (ctx.tree.as_str(), ctx.garden.as_deref()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("git", Some("cola")),
+            ("cola", Some("cola")),
+            ("python/qtpy", Some("cola")),
+            ("git", Some("git")),
+            ("cola", Some("git")),
+            ("python/qtpy", Some("git")),
+        ],
+        contexts
+    );
+}
+
 #[test]
 fn trees_from_pattern() {
     let config = common::garden_config();
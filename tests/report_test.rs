@@ -0,0 +1,23 @@
+use garden::model::OutputFormat;
+use garden::report;
+
+use yaml_rust::YamlLoader;
+
+/// Reals are formatted as valid JSON values.
+#[test]
+fn format_document_json_reals() {
+    let docs =
+        YamlLoader::load_from_str("[1.5, 1e3, .5, -2.0e-3, .inf, -.inf, .nan]").unwrap_or_default();
+    let json = report::format_document(&docs[0], OutputFormat::Json);
+    let expect = r#"[
+  1.5,
+  1000.0,
+  0.5,
+  -0.002,
+  ".inf",
+  "-.inf",
+  ".nan"
+]
+"#;
+    assert_eq!(expect, json);
+}
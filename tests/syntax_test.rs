@@ -24,6 +24,27 @@ fn is_tree() {
     assert!(!syntax::is_tree("tree"), "tree is not a tree");
 }
 
+#[test]
+fn is_compound_query() {
+    assert!(syntax::is_compound_query("a,b"), "a,b is compound");
+    assert!(
+        syntax::is_compound_query(":ci & %linux"),
+        ":ci & %linux is compound"
+    );
+    assert!(
+        syntax::is_compound_query("%backend - legacy-*"),
+        "exclusion is compound"
+    );
+    assert!(
+        !syntax::is_compound_query("legacy-*"),
+        "legacy-* is not compound"
+    );
+    assert!(
+        !syntax::is_compound_query("%group"),
+        "%group is not compound"
+    );
+}
+
 #[test]
 fn is_git_dir() {
     assert!(syntax::is_git_dir("tree.git"), "tree.git is a git dir");